@@ -1,8 +1,48 @@
-use std::{sync::mpsc, thread};
+use std::{path::PathBuf, sync::mpsc, thread, time::Duration};
 
 use windows_volume_control::{AudioController, CoinitMode};
-use stoppable_thread;
+mod api;
+mod commands;
+mod constants;
+mod debounce;
+mod events;
 mod functions;
+mod history;
+mod hooks;
+mod hotkeys;
+mod journal;
+mod logger;
+mod matchers;
+mod outcomes;
+mod process_watch;
+mod profiles;
+mod scripting;
+mod session_volume;
+mod settings;
+mod sources;
+mod timeline;
+mod tray;
+
+use constants::*;
+use api::{ApiRequest, ApiServer};
+use commands::ControllerCommand;
+use events::{ControllerEvent, ControllerStatus};
+use history::MatchRecord;
+use hooks::{Hook, HookConfig, HookRunner, HookTrigger};
+use hotkeys::{HotkeyConfig, Hotkeys};
+use journal::Journal;
+use matchers::{MatchEvent, MatcherRule, Matchers};
+use outcomes::{Outcome, OutcomeAction, OutcomeEffect, RoundContext};
+use process_watch::{GameProcessEvent, ProcessWatcher};
+use profiles::{Profile, ProfileRule, ProfileSet, RoundRule, MAX_FADE_DURATION_MS};
+use scripting::Script;
+use sources::SourceKind;
+use timeline::Timeline;
+use tray::{Tray, TrayAction};
+
+pub const APP_NAME: &str = "Valorant Music Controller (GUI)";
+
+pub use logger::init as init_logger;
 
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)]
@@ -29,13 +69,121 @@ pub struct ValorantMusicControllerApp {
     about_clicked: bool,
     #[serde(skip)]
     instructions_clicked: bool,
+    #[serde(skip)]
+    log_viewer_open: bool,
+    #[serde(skip)]
+    log_level_filter: log::LevelFilter,
+    #[serde(skip)]
+    log_search: String,
 
     #[serde(skip)]
-    program_thread: Option<stoppable_thread::StoppableHandle<()>>,
+    program_thread: Option<stoppable_thread::StoppableHandle<f32>>,
     #[serde(skip)]
-    receiver: Option<mpsc::Receiver<()>>,
+    receiver: Option<mpsc::Receiver<f32>>,
 
-    volumes: [u8; 3],
+    #[serde(skip)]
+    events_receiver: mpsc::Receiver<ControllerEvent>,
+    #[serde(skip)]
+    status: ControllerStatus,
+    #[serde(skip)]
+    timeline: Timeline,
+    #[serde(skip)]
+    timeline_open: bool,
+    #[serde(skip)]
+    profile_rules_open: bool,
+    #[serde(skip)]
+    profile_name: String, // Nombre del perfil elegido que se está editando; se aplica al terminar de editarlo si es válido
+    #[serde(skip)]
+    profile_name_error: Option<String>,
+    #[serde(skip)]
+    hotkeys: Hotkeys,
+    #[serde(skip)]
+    hotkeys_open: bool,
+    #[serde(skip)]
+    hotkey_errors: Vec<String>,
+    #[serde(skip)]
+    hook_runner: Option<HookRunner>,
+    #[serde(skip)]
+    hooks_open: bool,
+    #[serde(skip)]
+    outcomes_open: bool,
+    #[serde(skip)]
+    script_open: bool,
+    #[serde(skip)]
+    script_source: String,
+    #[serde(skip)]
+    script_message: Option<Result<String, String>>,
+    #[serde(skip)]
+    matchers_open: bool,
+    #[serde(skip)]
+    matcher_rules: Vec<MatcherRule>, // Reglas del usuario que se están editando
+    #[serde(skip)]
+    matcher_message: Option<Result<String, String>>,
+    #[serde(skip)]
+    matcher_test_line: String,
+    #[serde(skip)]
+    round_winner_known: bool, // Si la fuente y las reglas del log dan el ganador de cada ronda (marcador y punto de partido)
+    #[serde(skip)]
+    player_team_known: bool, // Si dan el equipo del jugador (resultados de las rondas y la partida)
+    #[serde(skip)]
+    tray: Option<Tray>,
+    #[serde(skip)]
+    start_hidden_pending: bool,
+    #[serde(skip)]
+    quitting: bool, // Si se ha pedido cerrar la aplicación de verdad, no ocultarla en la bandeja
+    #[serde(skip)]
+    process_watcher: Option<ProcessWatcher>,
+    #[serde(skip)]
+    auto_started: bool, // Si el programa se activó al abrir el juego, para pararlo al cerrarlo
+    #[serde(skip)]
+    api_server: Option<ApiServer>,
+    #[serde(skip)]
+    api_error: Option<String>,
+    #[serde(skip)]
+    api_open: bool,
+    #[serde(skip)]
+    settings_open: bool,
+    #[serde(skip)]
+    settings_result: Option<String>,
+    #[serde(skip)]
+    history_open: bool,
+    #[serde(skip)]
+    history_records: Vec<MatchRecord>,
+    #[serde(skip)]
+    history_export_result: Option<String>,
+
+    #[serde(skip)]
+    pending_journal: Option<Journal>,
+    #[serde(skip)]
+    journal_restore_failed: bool,
+
+    // Ajustes de versiones anteriores a los perfiles; solo se leen para pasarlos al primer perfil
+    #[serde(rename = "volumes", deserialize_with = "deserialize_some", skip_serializing)]
+    legacy_volumes: Option<[u8; 3]>,
+    #[serde(rename = "relative_mode", deserialize_with = "deserialize_some", skip_serializing)]
+    legacy_relative_mode: Option<bool>,
+
+    profiles: Vec<Profile>,
+    selected_profile: usize,
+    profile_rules: Vec<ProfileRule>,
+    hotkey_config: HotkeyConfig,
+    hook_config: HookConfig,
+    outcome_actions: Vec<OutcomeAction>,
+    script_enabled: bool,
+    auto_restore_volume: bool,
+    show_status_panel: bool,
+    log_to_file: bool,
+    log_path: String, // Vacío para usar la ruta por defecto
+    minimize_to_tray: bool,
+    close_to_tray: bool,
+    start_minimized: bool,
+    auto_start: bool, // Activar el programa al abrir el juego
+    game_state_source: SourceKind,
+    api_enabled: bool,
+    api_port: u16,
+    api_token: String,
+    history_export_path: String,
+    settings_path: String,
 }
 
 impl Default for ValorantMusicControllerApp {
@@ -61,22 +209,325 @@ impl Default for ValorantMusicControllerApp {
 
             about_clicked: false,
             instructions_clicked: false,
+            log_viewer_open: false,
+            log_level_filter: log::LevelFilter::Info,
+            log_search: String::new(),
 
             program_thread: None,
             receiver: None,
 
-            volumes: [100, 50, 0],
+            events_receiver: events::subscribe(),
+            status: ControllerStatus::default(),
+            timeline: Timeline::default(),
+            timeline_open: false,
+            profile_rules_open: false,
+            profile_name: String::new(),
+            profile_name_error: None,
+            hotkeys: Hotkeys::default(),
+            hotkeys_open: false,
+            hotkey_errors: Vec::new(),
+            hook_runner: None,
+            hooks_open: false,
+            outcomes_open: false,
+            script_open: false,
+            script_source: String::new(),
+            script_message: None,
+            matchers_open: false,
+            matcher_rules: Vec::new(),
+            matcher_message: None,
+            matcher_test_line: String::new(),
+            round_winner_known: false,
+            player_team_known: false,
+            tray: None,
+            start_hidden_pending: false,
+            quitting: false,
+            process_watcher: None,
+            auto_started: false,
+            api_server: None,
+            api_error: None,
+            api_open: false,
+            settings_open: false,
+            settings_result: None,
+            history_open: false,
+            history_records: Vec::new(),
+            history_export_result: None,
+
+            pending_journal: None,
+            journal_restore_failed: false,
+
+            legacy_volumes: None,
+            legacy_relative_mode: None,
+
+            profiles: vec![Profile::default()],
+            selected_profile: 0,
+            profile_rules: Vec::new(),
+            hotkey_config: HotkeyConfig::default(),
+            hook_config: HookConfig::default(),
+            outcome_actions: Vec::new(),
+            script_enabled: false,
+            auto_restore_volume: false,
+            show_status_panel: true,
+            log_to_file: false,
+            log_path: String::new(),
+            minimize_to_tray: true,
+            close_to_tray: false,
+            start_minimized: false,
+            auto_start: false,
+            game_state_source: SourceKind::default(),
+            api_enabled: false,
+            api_port: api::DEFAULT_PORT,
+            api_token: String::new(),
+            history_export_path: default_export_path("match_history.csv"),
+            settings_path: default_export_path("valorant_music_controller.toml"),
         }
     }
 }
 
 impl ValorantMusicControllerApp {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        if let Some(storage) = cc.storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+        let mut app: Self = match cc.storage {
+            Some(storage) => eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default(),
+            None => Default::default(),
+        };
+
+        // Los ajustes de volumen de versiones anteriores pasan a ser el primer perfil
+        if let Some(volumes) = app.legacy_volumes.take() {
+            app.profiles[0].volumes = volumes.to_vec();
+        }
+        if let Some(relative_mode) = app.legacy_relative_mode.take() {
+            app.profiles[0].relative_mode = relative_mode;
+        }
+        if app.profiles.is_empty() {
+            app.profiles.push(Profile::default());
+        }
+        app.profiles.iter_mut().for_each(Profile::normalize);
+        app.select_profile(app.selected_profile.min(app.profiles.len() - 1));
+
+        logger::set_file_path(custom_log_path(&app.log_path));
+        logger::set_file_logging(app.log_to_file);
+        app.hotkey_errors = app.hotkeys.apply(&mut app.hotkey_config);
+        app.hook_runner = HookRunner::start(app.hook_config.clone());
+        app.update_known_results();
+
+        if app.api_token.is_empty() {
+            app.api_token = api::generate_token();
+        }
+        app.restart_api(&cc.egui_ctx);
+
+        app.tray = Tray::new(cc);
+        app.process_watcher = process_watch::system().map(|processes| ProcessWatcher::start(processes, process_watch::GAME_PROCESS, cc.egui_ctx.clone()));
+        app.start_hidden_pending = app.start_minimized && app.tray.is_some();
+
+        // Si existe el registro de volumen es que la última ejecución no terminó correctamente
+        app.pending_journal = Journal::load();
+        if app.pending_journal.is_some() && app.auto_restore_volume {
+            app.restore_journal();
+        }
+
+        app
+    }
+
+    fn profile(&mut self) -> &mut Profile {
+        &mut self.profiles[self.selected_profile]
+    }
+
+    fn settings(&self) -> settings::Settings {
+        settings::Settings {
+            profiles: self.profiles.clone(),
+            selected_profile: self.selected_profile,
+            rules: self.profile_rules.clone(),
+            log_to_file: self.log_to_file,
+            log_path: self.log_path.clone(),
+            minimize_to_tray: self.minimize_to_tray,
+            close_to_tray: self.close_to_tray,
+            start_minimized: self.start_minimized,
+            game_state_source: self.game_state_source,
         }
+    }
 
-        Default::default()
+    fn apply_settings(&mut self, settings: settings::Settings) {
+        self.profiles = settings.profiles;
+        self.profile_rules = settings.rules;
+        self.log_to_file = settings.log_to_file;
+        self.log_path = settings.log_path;
+        self.minimize_to_tray = settings.minimize_to_tray;
+        self.close_to_tray = settings.close_to_tray;
+        self.start_minimized = settings.start_minimized;
+        self.game_state_source = settings.game_state_source;
+        self.select_profile(settings.selected_profile);
+        self.update_known_results();
+
+        logger::set_file_path(custom_log_path(&self.log_path));
+        logger::set_file_logging(self.log_to_file);
+    }
+
+    // Resultados de la partida que se pueden conocer; solo los dan las reglas del log del usuario, no la API del Riot Client
+    fn update_known_results(&mut self) {
+        let matchers = Matchers::load();
+        let log = self.game_state_source != SourceKind::RiotApi;
+        self.round_winner_known = log && matchers.detects_round_winner();
+        self.player_team_known = log && matchers.detects_player_team();
+    }
+
+    // Carga el script en el editor; si todavía no existe se empieza con la plantilla
+    fn open_script_editor(&mut self) {
+        self.script_source = scripting::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .unwrap_or_else(|| scripting::TEMPLATE.to_owned());
+        self.script_message = None;
+        self.script_open = true;
+    }
+
+    fn save_script(&mut self) {
+        let result = scripting::check(&self.script_source).and_then(|_| {
+            let path = scripting::path().ok_or("There is no folder to save the script")?;
+            std::fs::write(&path, &self.script_source).map_err(|err| format!("Couldn't save {}: {}", path.display(), err))
+        });
+        self.script_message = Some(result.map(|_| "Script saved".to_owned()));
+    }
+
+    // Arranca el servidor de la API si está activado, parando el anterior si lo había
+    fn restart_api(&mut self, ctx: &egui::Context) {
+        self.api_server = None;
+        self.api_error = None;
+        if !self.api_enabled { return; }
+
+        match ApiServer::start(self.api_port, self.api_token.clone(), self.status.clone(), ctx.clone()) {
+            Ok(server) => self.api_server = Some(server),
+            Err(err) => {
+                log::error!("{}", err);
+                self.api_error = Some(err);
+            }
+        }
+    }
+
+    // Perfiles que se pasan al hilo del controlador
+    fn profile_set(&self) -> ProfileSet {
+        ProfileSet { profiles: self.profiles.clone(), selected: self.selected_profile, rules: self.profile_rules.clone() }
+    }
+
+    fn select_process(&mut self, index: i8) {
+        self.selected_process_index = index;
+        self.button_enabled = true;
+        self.button_label = get_activate_button_label(self.simulation_checked);
+        self.initial_process_volume = unsafe { self.audio_controller.get_session_by_name(self.process_list[index as usize].clone()).unwrap().getVolume() };
+    }
+
+    // Cambia de perfil y selecciona su proceso si está en la lista
+    fn select_profile(&mut self, index: usize) {
+        self.selected_profile = index;
+        self.profile_name = self.profiles[index].name.clone();
+        self.profile_name_error = None;
+        let Some(process_name) = self.profiles[index].process_name.clone() else { return; };
+        if let Some(process_index) = self.process_list.iter().position(|name| *name == process_name) {
+            self.select_process(process_index as i8);
+        }
+    }
+
+    // Renombra el perfil elegido con el nombre editado; las reglas siguen apuntando al perfil renombrado
+    fn rename_profile(&mut self) {
+        let name = self.profile_name.clone();
+        if let Err(err) = profiles::check_name(&self.profiles, self.selected_profile, &name) {
+            self.profile_name_error = Some(err);
+            return;
+        }
+        self.profile_name_error = None;
+        let old_name = std::mem::replace(&mut self.profiles[self.selected_profile].name, name.clone());
+        self.profile_rules.iter_mut().filter(|rule| rule.profile == old_name).for_each(|rule| rule.profile = name.clone());
+    }
+
+    // Activa o para el programa (o simula una partida) con el proceso y los perfiles elegidos
+    fn toggle_program(&mut self) {
+        if self.program_active { self.stop_program(); }
+        else { self.start_program(self.simulation_checked); }
+    }
+
+    // Activación y parada pedidas desde fuera de la ventana (bandeja del sistema, API); nunca se simula una partida
+    fn request_start(&mut self) {
+        if self.program_active { return; }
+        if self.selected_process_index == -1 {
+            log::warn!("Can't activate the program: no process is selected");
+            return;
+        }
+        self.start_program(false);
+    }
+
+    fn request_stop(&mut self) {
+        // Las simulaciones terminan solas
+        if self.program_thread.is_some() { self.stop_program(); }
+    }
+
+    fn start_program(&mut self, simulate: bool) {
+        // El controlador escribe su propio registro de volumen, que sustituiría al de la ejecución anterior sin restaurarlo
+        if self.pending_journal.is_some() {
+            log::warn!("Can't activate the program: restore or dismiss the volume left by the previous run first");
+            return;
+        }
+        self.program_active = true;
+        let process_name = self.process_list[self.selected_process_index as usize].clone();
+        let profiles = self.profile_set();
+        let script = if self.script_enabled { Script::load(self.hook_config.clone()) } else { None };
+        let outcome_actions = self.outcome_actions.clone();
+        let source = self.game_state_source;
+        if simulate {
+            let (tx, rx) = mpsc::channel(); // Canal para comunicarse con el hilo secundario
+            self.receiver = Some(rx); // Guardamos el receptor
+
+            self.button_label = "Simulating...".to_owned();
+            self.button_enabled = false;
+
+            thread::spawn(move || {
+                tx.send(functions::simulate_match(process_name, profiles, script, outcome_actions)).unwrap();
+            });
+        }
+        else {
+            self.program_thread = Some(stoppable_thread::spawn(move |should_stop| { // Crear un nuevo hilo para ejecutar el programa
+                functions::main_function(should_stop, process_name, profiles, script, outcome_actions, source)
+            }));
+            self.button_label = "Stop program".to_owned();
+        }
+    }
+
+    fn stop_program(&mut self) {
+        self.program_active = false;
+        self.button_label = "Stopping program...".to_owned();
+        self.button_enabled = false;
+        self.auto_started = false;
+        self.initial_process_volume = self.program_thread.take().unwrap().stop().join().unwrap(); // Esperar a que el hilo termine
+        self.button_label = get_activate_button_label(self.simulation_checked);
+        self.button_enabled = true;
+        self.restore_process_volume();
+    }
+
+    // Restaura el volumen original del proceso seleccionado y borra el registro de volumen
+    fn restore_process_volume(&mut self) {
+        unsafe {
+            if let Some(session) = self.audio_controller.get_session_by_name(self.process_list[self.selected_process_index as usize].clone()) {
+                session.setVolume(self.initial_process_volume);
+            }
+        }
+        Journal::clear();
+    }
+
+    // Restaura el volumen (y la reproducción si se pausó) que dejó una ejecución anterior que no terminó correctamente
+    fn restore_journal(&mut self) {
+        let Some(journal) = self.pending_journal.clone() else { return; };
+
+        unsafe {
+            match self.audio_controller.get_session_by_name(journal.process_name.clone()) {
+                Some(session) => session.setVolume(journal.original_volume),
+                None => {
+                    self.journal_restore_failed = true;
+                    return;
+                }
+            }
+        }
+        if journal.paused { functions::playOrPauseMedia(); }
+        log::info!("Restored the volume of {} to {:.0}%", journal.process_name, journal.original_volume * 100.0);
+
+        Journal::clear();
+        self.pending_journal = None;
+        self.journal_restore_failed = false;
     }
 }
 
@@ -96,7 +547,7 @@ impl eframe::App for ValorantMusicControllerApp {
             ctx,
             "dialog_how_simulation_works".to_owned(),
             "How simulation works?".to_owned(),
-            "Simulating a match does not require Valorant to be opened. Every second, the simulation will change the volume using this state template: Not in game -> Buy phase -> Round started -> Buy phase -> Match ended.".to_owned()
+            "Simulating a match does not require Valorant to be opened. Every second, the simulation will change the volume using this state template: Menus -> Agent select -> Loading -> Buy phase -> Round started -> Buy phase -> Match ended.".to_owned()
         );
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -105,14 +556,79 @@ impl eframe::App for ValorantMusicControllerApp {
                 let is_web = cfg!(target_arch = "wasm32");
                 if !is_web {
                     ui.menu_button("File", |ui| {
+                        if ui.button("Hotkeys").clicked() {
+                            self.hotkeys_open = true;
+                        }
+                        if ui.button("Hooks").clicked() {
+                            self.hooks_open = true;
+                        }
+                        if ui.add_enabled(self.player_team_known, egui::Button::new("Outcome actions"))
+                            .on_disabled_hover_text("Wins and losses are only known with your own log matchers for your team, because the game log doesn't show it by default. See \"Log matchers\".")
+                            .clicked() {
+                            self.outcomes_open = true;
+                        }
+                        if ui.button("Script").clicked() {
+                            self.open_script_editor();
+                        }
+                        if ui.button("Log matchers").clicked() {
+                            self.matcher_message = None;
+                            self.matcher_rules = matchers::load_user().unwrap_or_else(|err| {
+                                self.matcher_message = Some(Err(err));
+                                Vec::new()
+                            });
+                            self.matchers_open = true;
+                        }
+                        if ui.button("Remote API").clicked() {
+                            self.api_open = true;
+                        }
+                        if ui.button("Import/export settings").clicked() {
+                            self.settings_open = true;
+                            self.settings_result = None;
+                        }
+                        ui.menu_button("Game state source", |ui| {
+                            for source in SourceKind::ALL {
+                                if ui.radio_value(&mut self.game_state_source, source, source.name()).on_hover_text(source.description()).changed() {
+                                    self.update_known_results();
+                                }
+                            }
+                            if self.program_active {
+                                ui.label("Applied the next time the program is activated");
+                            }
+                        });
+                        ui.separator();
+                        ui.add_enabled_ui(self.tray.is_some(), |ui| {
+                            ui.checkbox(&mut self.minimize_to_tray, "Minimize to tray")
+                                .on_hover_text("Minimizing the window hides it in the system tray.");
+                            ui.checkbox(&mut self.close_to_tray, "Close to tray")
+                                .on_hover_text("Closing the window hides it in the system tray. Use Quit to close the program.");
+                            ui.checkbox(&mut self.start_minimized, "Start minimized to tray");
+                        }).response.on_disabled_hover_text("The system tray is not available");
+                        ui.add_enabled(self.process_watcher.is_some(), egui::Checkbox::new(&mut self.auto_start, "Start with the game"))
+                            .on_hover_text("Activate the program when VALORANT starts and stop it when the game exits. A process must be selected.")
+                            .on_disabled_hover_text("The game process can't be detected on this system");
+                        ui.separator();
                         if ui.button("Quit").clicked() {
+                            self.quitting = true;
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
                     });
+                    ui.menu_button("View", |ui| {
+                        ui.checkbox(&mut self.show_status_panel, "Status panel");
+                        if ui.button("Volume timeline").clicked() {
+                            self.timeline_open = true;
+                        }
+                        if ui.button("Match history").clicked() {
+                            self.history_records = history::load();
+                            self.history_open = true;
+                        }
+                    });
                     ui.menu_button("Help", |ui| {
                         if ui.button("How to use?").clicked() {
                             self.instructions_clicked = true;
                         }
+                        if ui.button("Log viewer").clicked() {
+                            self.log_viewer_open = true;
+                        }
                         if ui.button("About").clicked() {
                             self.about_clicked = true;   
                         }
@@ -124,15 +640,299 @@ impl eframe::App for ValorantMusicControllerApp {
             });
         });
 
+        // Bandeja del sistema
+        let tray_actions = self.tray.as_ref().map(|tray| tray.actions()).unwrap_or_default();
+        for action in tray_actions {
+            match action {
+                TrayAction::Show => {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                }
+                TrayAction::Toggle if self.program_active => self.request_stop(),
+                TrayAction::Toggle => self.request_start(),
+                TrayAction::SelectProfile(index) if index < self.profiles.len() => {
+                    // Con el programa activo, el controlador cambia de perfil y lo notifica con un evento
+                    if self.program_active { commands::send(ControllerCommand::SelectProfile(index)); }
+                    else { self.select_profile(index); }
+                }
+                TrayAction::SelectProfile(_) => {}
+                TrayAction::Quit => {
+                    self.quitting = true;
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            }
+        }
+        // Apertura y cierre del juego
+        let process_events = self.process_watcher.as_ref().map(|watcher| watcher.events()).unwrap_or_default();
+        for event in process_events {
+            match event {
+                GameProcessEvent::Started if self.auto_start && !self.program_active => {
+                    log::info!("The game has started, activating the program");
+                    self.request_start();
+                    self.auto_started = self.program_thread.is_some();
+                }
+                GameProcessEvent::Started => {}
+                // Si se activó al abrir el juego se para; si no, el controlador sigue activo pero sale de la partida
+                GameProcessEvent::Exited if self.auto_started => {
+                    log::info!("The game has exited, stopping the program");
+                    self.request_stop();
+                }
+                GameProcessEvent::Exited => { commands::send(ControllerCommand::GameExited); }
+            }
+        }
+        // Peticiones de la API
+        let api_requests = self.api_server.as_ref().map(|server| server.requests()).unwrap_or_default();
+        for request in api_requests {
+            match request {
+                ApiRequest::Start => self.request_start(),
+                ApiRequest::Stop => self.request_stop(),
+                ApiRequest::SelectProfile(name) => match self.profiles.iter().position(|profile| profile.name == name) {
+                    Some(index) if self.program_active => { commands::send(ControllerCommand::SelectProfile(index)); }
+                    Some(index) => self.select_profile(index),
+                    None => log::warn!("API: there is no profile named {}", name),
+                },
+            }
+        }
+
+        // Al minimizar se oculta en la bandeja; al cerrar solo si se ha elegido "Close to tray", si no el programa se cierra
+        if ctx.input(|i| i.viewport().minimized == Some(true)) && self.minimize_to_tray && self.tray.is_some() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+        }
+        if ctx.input(|i| i.viewport().close_requested()) && self.close_to_tray && self.tray.is_some() && !self.quitting {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+        }
+        // La ventana se oculta después del primer fotograma porque eframe la muestra al dibujarlo
+        if self.start_hidden_pending && ctx.frame_nr() > 0 {
+            self.start_hidden_pending = false;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+        }
+
+        // Eventos del hilo del controlador
+        while let Ok(event) = self.events_receiver.try_recv() {
+            self.status.apply(&event);
+            self.timeline.apply(&event);
+
+            // Cambios en los perfiles hechos desde el controlador (atajos de teclado)
+            match &event {
+                ControllerEvent::ProfileSelected(index) if *index < self.profiles.len() => {
+                    self.selected_profile = *index;
+                    self.profile_name = self.profiles[*index].name.clone();
+                }
+                ControllerEvent::LevelChanged { profile, state, level } => {
+                    if let Some(profile) = self.profiles.iter_mut().find(|p| p.name == *profile) {
+                        profile.volumes[*state as usize] = *level;
+                    }
+                }
+                _ => {}
+            }
+
+            // Al terminar una partida se recarga el historial por si está abierto
+            let match_ended = matches!(event, ControllerEvent::StateChanged { prev, next } if States::in_match(prev) && !States::in_match(next));
+            if (match_ended || matches!(event, ControllerEvent::Stopped)) && self.history_open {
+                self.history_records = history::load();
+            }
+        }
+        if self.program_active {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        if let Some(tray) = self.tray.as_mut() {
+            tray.update(self.program_active.then_some(self.status.state), self.program_active, &self.profiles, self.selected_profile);
+        }
+
+        egui::TopBottomPanel::bottom("status_panel")
+        .resizable(true)
+        .show_animated(ctx, self.show_status_panel, |ui| {
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                ui.strong("Status:");
+                if self.program_active {
+                    ui.label(States::name(self.status.state));
+                    if self.status.suspended {
+                        ui.colored_label(ui.visuals().warn_fg_color, "(suspended)");
+                    }
+                    ui.separator();
+                    ui.label(match self.status.round {
+                        Some(round) => format!("Round {}", round),
+                        None => "No round".to_owned(),
+                    });
+                    if self.round_winner_known && States::in_match(self.status.state) && self.status.score != Default::default() {
+                        ui.separator();
+                        ui.label(format!("Score: {}", self.status.score.describe()));
+                        if let Some(side) = self.status.round.and_then(|round| self.status.score.side(round, self.status.mode)) {
+                            ui.label(side.name());
+                        }
+                    }
+                    if let Some(round) = self.status.round.filter(|_| States::in_match(self.status.state)) {
+                        for context in RoundContext::ALL.into_iter().filter(|context| context.applies(round, &self.status.score, self.status.mode) && (self.round_winner_known || *context != RoundContext::MatchPoint)) {
+                            ui.colored_label(ui.visuals().warn_fg_color, context.name());
+                        }
+                    }
+                }
+                else {
+                    ui.label("Inactive");
+                }
+            });
+            if self.program_active {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Profile: {}", self.status.profile.as_deref().unwrap_or(&self.profiles[self.selected_profile].name)));
+                    if let Some(source) = &self.status.source {
+                        ui.separator();
+                        ui.label(format!("Source: {}", source));
+                    }
+                    if self.status.mode.is_some() || self.status.map.is_some() {
+                        ui.separator();
+                        ui.label(format!(
+                            "{} on {}",
+                            self.status.mode.map_or("Unknown mode", |mode| mode.name()),
+                            self.status.map.as_deref().unwrap_or("unknown map")
+                        ));
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label(format!("Volume: {:.0}%", self.status.volume * 100.0));
+                ui.add(egui::ProgressBar::new(self.status.fade_progress).text(format!("Target: {:.0}%", self.status.target_volume * 100.0)));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Last line:");
+                ui.add(egui::Label::new(egui::RichText::new(self.status.last_line.clone().unwrap_or_default()).monospace().small()).truncate());
+            });
+            egui::ScrollArea::vertical()
+            .auto_shrink([false, true])
+            .max_height(80.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for (time, description) in self.status.recent_events.iter() {
+                    ui.label(format!("[{}] {}", time, description));
+                }
+            });
+            ui.add_space(4.0);
+        });
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            let mut profile_selected = None;
+            ui.add_enabled_ui(!self.program_active, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Profile:");
+                    egui::ComboBox::from_id_source("profile_selector")
+                    .selected_text(self.profiles[self.selected_profile].name.clone())
+                    .show_ui(ui, |ui| {
+                        for (index, profile) in self.profiles.iter().enumerate() {
+                            if ui.selectable_label(index == self.selected_profile, profile.name.clone()).clicked() {
+                                profile_selected = Some(index);
+                            }
+                        }
+                    });
+                    if ui.add(egui::TextEdit::singleline(&mut self.profile_name).desired_width(120.0)).lost_focus() {
+                        self.rename_profile();
+                    }
+                    if ui.button("New").clicked() {
+                        let name = profiles::unique_name(&self.profiles, "New profile");
+                        self.profiles.push(Profile { name, ..Default::default() });
+                        profile_selected = Some(self.profiles.len() - 1);
+                    }
+                    if ui.button("Duplicate").clicked() {
+                        let mut profile = self.profiles[self.selected_profile].clone();
+                        profile.name = profiles::unique_name(&self.profiles, &profile.name);
+                        self.profiles.push(profile);
+                        profile_selected = Some(self.profiles.len() - 1);
+                    }
+                    if ui.add_enabled(self.profiles.len() > 1, egui::Button::new("Delete")).clicked() {
+                        let removed = self.profiles.remove(self.selected_profile);
+                        self.profile_rules.retain(|rule| rule.profile != removed.name);
+                        profile_selected = Some(self.selected_profile.min(self.profiles.len() - 1));
+                    }
+                    if ui.button("Rules").on_hover_text("Choose a profile automatically depending on the game mode and map").clicked() {
+                        self.profile_rules_open = true;
+                    }
+                });
+            });
+            if let Some(err) = &self.profile_name_error {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+            }
+            if let Some(index) = profile_selected {
+                self.select_profile(index);
+            }
+            ui.separator();
+
             egui::Grid::new("grid_primary").min_col_width(0.0).show(ui, |ui| {
                 ui.vertical(|ui| {
                     ui.style_mut().spacing.item_spacing = egui::vec2(0.0, 10.0);
                     ui.heading("Volume control");
                     ui.style_mut().spacing.item_spacing = egui::vec2(7.5, 8.0);
-                    ui.add_enabled(!self.program_active, egui::Slider::new(&mut self.volumes[0], 1..=100).max_decimals(0).text("Not in game").custom_formatter(|value, _| format!("{}%", value)));
-                    ui.add_enabled(!self.program_active, egui::Slider::new(&mut self.volumes[1], 0..=100).max_decimals(0).text("In game: Buy phase").custom_formatter(|value, _| format!("{}%", value)));
-                    ui.add_enabled(!self.program_active, egui::Slider::new(&mut self.volumes[2], 0..=100).max_decimals(0).text("In game: Playing").custom_formatter(|value, _| format!("{}%", value)));
+                    let program_active = self.program_active;
+                    let round_winner_known = self.round_winner_known;
+                    let profile = self.profile();
+                    ui.add_enabled(!program_active, egui::Slider::new(&mut profile.volumes[0], 1..=100).max_decimals(0).text("Not in game").custom_formatter(|value, _| format!("{}%", value)));
+                    ui.add_enabled(!program_active, egui::Slider::new(&mut profile.volumes[1], 0..=100).max_decimals(0).text("In game: Buy phase").custom_formatter(|value, _| format!("{}%", value)));
+                    ui.add_enabled(!program_active, egui::Slider::new(&mut profile.volumes[2], 0..=100).max_decimals(0).text("In game: Playing").custom_formatter(|value, _| format!("{}%", value)));
+                    egui::CollapsingHeader::new("Pregame states").show(ui, |ui| {
+                        ui.label(egui::RichText::new("Volumes before a match starts. Only used when the game state source shows these screens. In queue and Match found are only detected with the Riot Client API, or with your own log matchers.").small().weak());
+                        for state in States::ALL.into_iter().filter(|state| States::is_pregame(*state)) {
+                            let slider = ui.add_enabled(!program_active, egui::Slider::new(&mut profile.volumes[state as usize], 0..=100).max_decimals(0).text(States::name(state)).custom_formatter(|value, _| format!("{}%", value)));
+                            if matches!(state, States::IN_QUEUE | States::MATCH_FOUND) {
+                                slider.on_hover_text("Only detected when the game state source is the Riot Client API. The default log matchers don't detect this screen.");
+                            }
+                        }
+                    });
+                    ui.add_enabled(!program_active, egui::Slider::new(&mut profile.fade_duration_ms, 0..=MAX_FADE_DURATION_MS).step_by(100.0).text("Fade duration").custom_formatter(|value, _| format!("{:.1}s", value / 1000.0)));
+                    ui.add_enabled(!program_active, egui::Checkbox::new(&mut profile.relative_mode, "Relative to my volume"))
+                        .on_hover_text("Volumes are a percentage of the process volume you set. If you change it while the program is active, your new volume is kept.");
+                    ui.add_enabled(!program_active, egui::Checkbox::new(&mut profile.pause_on_zero, "Pause at 0%"))
+                        .on_hover_text("Pause the media player when a state with 0% volume is reached and resume it when exiting that state.");
+                    ui.add_enabled(!program_active, egui::Slider::new(&mut profile.early_fade_s, 0..=10).text("Fade before round").custom_formatter(|value, _| if value == 0.0 { "Off".to_owned() } else { format!("{}s", value) }))
+                        .on_hover_text("Start fading to the playing volume this many seconds before the buy phase is expected to end. If the round doesn't start on time, the buy phase volume is restored.");
+                    egui::CollapsingHeader::new("State delays").show(ui, |ui| {
+                        ui.label(egui::RichText::new("Time a state must last before its volume is applied. Quick changes in between are ignored.").small().weak());
+                        egui::Grid::new("state_delays").show(ui, |ui| {
+                            for state in States::ALL {
+                                ui.label(States::name(state));
+                                ui.add_enabled(!program_active, egui::DragValue::new(&mut profile.min_dwell_ms[state as usize]).range(0..=10000).speed(10.0).suffix(" ms"));
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    egui::CollapsingHeader::new("Round rules").show(ui, |ui| {
+                        ui.label(egui::RichText::new("Special volumes at some moments of the match, used instead of the volume of the state. The first matching rule is used.").small().weak());
+                        if !round_winner_known {
+                            ui.label(egui::RichText::new("Match point needs the winner of every round, which the game log only shows with your own log matchers.").small().weak());
+                        }
+                        let state_name = |state: Option<u8>| state.map_or("Any state", States::name);
+                        let mut removed = None;
+                        ui.add_enabled_ui(!program_active, |ui| {
+                            for (index, rule) in profile.round_rules.iter_mut().enumerate() {
+                                ui.push_id(index, |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut rule.enabled, "");
+                                        egui::ComboBox::from_id_source("when").width(100.0).selected_text(rule.when.name()).show_ui(ui, |ui| {
+                                            let current = rule.when;
+                                            for when in RoundContext::ALL.into_iter().filter(|when| round_winner_known || *when != RoundContext::MatchPoint || *when == current) {
+                                                ui.selectable_value(&mut rule.when, when, when.name());
+                                            }
+                                        });
+                                        egui::ComboBox::from_id_source("state").width(90.0).selected_text(state_name(rule.state)).show_ui(ui, |ui| {
+                                            ui.selectable_value(&mut rule.state, None, state_name(None));
+                                            for state in States::ALL.into_iter().filter(|state| States::in_match(*state)) {
+                                                ui.selectable_value(&mut rule.state, Some(state), States::name(state));
+                                            }
+                                        });
+                                        ui.add(egui::DragValue::new(&mut rule.volume).range(0..=100).suffix("%"));
+                                        if ui.button("🗑").on_hover_text("Delete").clicked() {
+                                            removed = Some(index);
+                                        }
+                                    });
+                                });
+                            }
+                            if let Some(index) = removed {
+                                profile.round_rules.remove(index);
+                            }
+                            if ui.button("Add rule").clicked() {
+                                profile.round_rules.push(RoundRule::default());
+                            }
+                        });
+                    });
                 });
                 ui.add(egui::Separator::default().vertical());
                 ui.vertical(|ui| {
@@ -159,26 +959,20 @@ impl eframe::App for ValorantMusicControllerApp {
                                 });
                                 
                                 row.response().on_hover_cursor(egui::CursorIcon::PointingHand);
-                                if row.response().clicked() || label_clicked {
-                                    if !self.program_active {
-                                        self.selected_process_index = i;
-                                        self.button_enabled = true;
-                                        self.button_label = get_activate_button_label(self.simulation_checked);
-                                        self.initial_process_volume = unsafe { self.audio_controller.get_session_by_name(self.process_list[i as usize].clone()).unwrap().getVolume() };
-                                    }
+                                if (row.response().clicked() || label_clicked) && !self.program_active {
+                                    self.select_process(i);
+                                    self.profiles[self.selected_profile].process_name = Some(self.process_list[i as usize].clone());
                                 }
                             });
                         }
                     });
 
-                    if ui.add_sized((ui.available_width(), 0.0), egui::Button::new("Update process list")).clicked() {
-                        if !self.program_active {
-                            self.audio_controller = unsafe { AudioController::init(Some(CoinitMode::ApartmentThreaded)) };
-                            self.process_list = get_process_list(&mut self.audio_controller);
-                            self.selected_process_index = -1;
-                            self.button_enabled = false;
-                            self.button_label = "Select a process".to_owned();
-                        }
+                    if ui.add_sized((ui.available_width(), 0.0), egui::Button::new("Update process list")).clicked() && !self.program_active {
+                        self.audio_controller = unsafe { AudioController::init(Some(CoinitMode::ApartmentThreaded)) };
+                        self.process_list = get_process_list(&mut self.audio_controller);
+                        self.selected_process_index = -1;
+                        self.button_enabled = false;
+                        self.button_label = "Select a process".to_owned();
                     }
                 });
                 ui.end_row();
@@ -198,43 +992,13 @@ impl eframe::App for ValorantMusicControllerApp {
                     }
                 }
                 
-                if ui.add_enabled(self.button_enabled, egui::Button::new(self.button_label.clone()).min_size(egui::vec2(ui.available_width(), 30.0))).clicked() {
-                    self.program_active = !self.program_active;
-                    if self.program_active { // Se activó el programa
-                        if self.simulation_checked {
-                            let process_name = self.process_list[self.selected_process_index as usize].clone();
-                            let volumes = self.volumes.clone();
-                            
-                            let (tx, rx) = mpsc::channel(); // Canal para comunicarse con el hilo secundario
-                            self.receiver = Some(rx); // Guardamos el receptor
-
-                            self.button_label = "Simulating...".to_owned();
-                            self.button_enabled = false;
-
-                            thread::spawn(move || {
-                                functions::simulate_match(process_name, volumes);
-                                tx.send(()).unwrap();
-                            });
-                        }
-                        else {
-                            let process_name = self.process_list[self.selected_process_index as usize].clone();
-                            let volumes = self.volumes.clone();
-                            self.program_thread = Some(stoppable_thread::spawn(move |should_stop| { // Crear un nuevo hilo para ejecutar el programa
-                                functions::main_function(should_stop, process_name, volumes);
-                            }));
-                            self.button_label = "Stop program".to_owned();
-                        }
-                    }
-                    else { // Se desactivó el programa
-                        self.button_label = "Stopping program...".to_owned();
-                        self.button_enabled = false;
-                        self.program_thread.take().unwrap().stop().join().unwrap(); // Esperar a que el hilo termine
-                        self.button_label = get_activate_button_label(self.simulation_checked);
-                        self.button_enabled = true;
-                        unsafe { // Restaurar el volumen del proceso
-                            self.audio_controller.get_session_by_name(self.process_list[self.selected_process_index as usize].clone()).unwrap().setVolume(self.initial_process_volume);
-                        }
-                    }
+                let journal_pending = self.pending_journal.is_some() && !self.program_active;
+                let mut button = ui.add_enabled(self.button_enabled && !journal_pending, egui::Button::new(self.button_label.clone()).min_size(egui::vec2(ui.available_width(), 30.0)));
+                if journal_pending {
+                    button = button.on_disabled_hover_text("Restore or dismiss the volume left by the previous run first");
+                }
+                if button.clicked() {
+                    self.toggle_program();
                 }
             });
         });
@@ -264,16 +1028,587 @@ impl eframe::App for ValorantMusicControllerApp {
             ui.label("3. Activate the program using the main button.");
             ui.label("4. If already not playing, start playing a video or music.");
             ui.add_space(8.0);
-            ui.label("Note: Setting a volume to 0 on a state will pause the media player when this state is reached and will resume it when exiting this state.");
+            ui.label("Note: Setting a volume to 0 on a state will pause the media player when this state is reached and will resume it when exiting this state, unless \"Pause at 0%\" is unchecked.");
+            ui.label("Each profile keeps its own volumes and process. Create one for each way you play and switch between them with the profile selector.");
+        });
+
+        egui::Window::new("Hotkeys")
+        .default_size(egui::vec2(380.0, 200.0))
+        .open(&mut self.hotkeys_open)
+        .show(ctx, |ui| {
+            ui.label("Global hotkeys work while the program is active, even if Valorant is focused.");
+            ui.add_space(4.0);
+            ui.checkbox(&mut self.hotkey_config.enabled, "Enable hotkeys");
+            ui.add_enabled_ui(self.hotkey_config.enabled, |ui| {
+                egui::Grid::new("hotkeys").show(ui, |ui| {
+                    for (description, key, _) in self.hotkey_config.bindings() {
+                        ui.label(description);
+                        ui.add(egui::TextEdit::singleline(key).hint_text("None").desired_width(140.0));
+                        ui.end_row();
+                    }
+                });
+            });
+            ui.label(egui::RichText::new("Examples: Ctrl+Alt+S, Ctrl+Shift+F9, Alt+Up. Leave a hotkey empty to disable it.").small().weak());
+            if ui.button("Apply").clicked() {
+                self.hotkey_errors = self.hotkeys.apply(&mut self.hotkey_config);
+            }
+            for error in self.hotkey_errors.iter() {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+        });
+
+        let mut hooks_open = self.hooks_open; // Se copia porque la ventana necesita acceso a todo self
+        egui::Window::new("Hooks")
+        .default_size(egui::vec2(520.0, 260.0))
+        .open(&mut hooks_open)
+        .show(ctx, |ui| {
+            ui.label("Run a command when something happens in the game, e.g. to switch OBS scenes or change the color of your lights. The output of the commands is shown in the log viewer.");
+            ui.add_space(4.0);
+            ui.checkbox(&mut self.hook_config.enabled, "Enable hooks");
+            ui.horizontal(|ui| {
+                ui.label("Timeout");
+                ui.add(egui::DragValue::new(&mut self.hook_config.timeout_secs).range(1..=600).suffix(" s"));
+                ui.label("Max. simultaneous commands");
+                ui.add(egui::DragValue::new(&mut self.hook_config.max_concurrent).range(1..=16));
+            });
+            ui.separator();
+
+            let state_name = |state: Option<u8>| state.map_or("Any state", States::name);
+            let player_team_known = self.player_team_known;
+            let (mut tested, mut removed) = (None, None);
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                for (index, hook) in self.hook_config.hooks.iter_mut().enumerate() {
+                    ui.push_id(index, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut hook.enabled, "");
+                            egui::ComboBox::from_id_source("trigger").selected_text(hook.trigger.name()).show_ui(ui, |ui| {
+                                // Los resultados solo se muestran si se pueden conocer, o si el comando ya usa uno
+                                let current = hook.trigger;
+                                for trigger in HookTrigger::ALL.into_iter().filter(|trigger| player_team_known || !matches!(trigger, HookTrigger::Outcome(_)) || *trigger == current) {
+                                    ui.selectable_value(&mut hook.trigger, trigger, trigger.name());
+                                }
+                            });
+                            if hook.trigger == HookTrigger::StateChanged {
+                                for (id, state) in [("from", &mut hook.from), ("to", &mut hook.to)] {
+                                    ui.label(id);
+                                    egui::ComboBox::from_id_source(id).selected_text(state_name(*state)).show_ui(ui, |ui| {
+                                        ui.selectable_value(state, None, state_name(None));
+                                        for value in States::ALL {
+                                            ui.selectable_value(state, Some(value), States::name(value));
+                                        }
+                                    });
+                                }
+                            }
+                            if ui.button("Test").on_hover_text("Run the command now with the current state").clicked() {
+                                tested = Some(index);
+                            }
+                            if ui.button("🗑").on_hover_text("Delete").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                        ui.add(egui::TextEdit::singleline(&mut hook.command).hint_text("Command").desired_width(f32::INFINITY).font(egui::TextStyle::Monospace));
+                    });
+                    ui.add_space(4.0);
+                }
+            });
+            if let Some(index) = tested {
+                hooks::test(&self.hook_config.hooks[index], &self.hook_config, &self.status);
+            }
+            if let Some(index) = removed {
+                self.hook_config.hooks.remove(index);
+            }
+            if ui.button("Add hook").clicked() {
+                self.hook_config.hooks.push(Hook::default());
+            }
+            ui.label(egui::RichText::new("Commands run in cmd on Windows and sh on other systems with these environment variables: VMC_EVENT, VMC_PREV_STATE, VMC_STATE, VMC_STATE_NAME, VMC_ROUND, VMC_SCORE, VMC_MAP, VMC_MODE, VMC_PROFILE and VMC_TIMESTAMP.").small().weak());
+            if ui.button("Apply").clicked() {
+                self.hook_runner = HookRunner::start(self.hook_config.clone());
+            }
+        });
+        self.hooks_open = hooks_open;
+
+        egui::Window::new("Outcome actions")
+        .default_size(egui::vec2(440.0, 220.0))
+        .open(&mut self.outcomes_open)
+        .show(ctx, |ui| {
+            ui.label("Change the music when you win or lose a round or the match. The changes are used the next time the program is activated.");
+            ui.label(egui::RichText::new("Results are only known when the game log shows the winner and your team, see \"File\" > \"Log matchers\".").small().weak());
+            ui.separator();
+
+            let mut removed = None;
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                for (index, action) in self.outcome_actions.iter_mut().enumerate() {
+                    ui.push_id(index, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut action.enabled, "");
+                            egui::ComboBox::from_id_source("outcome").selected_text(action.outcome.name()).show_ui(ui, |ui| {
+                                for outcome in Outcome::ALL {
+                                    ui.selectable_value(&mut action.outcome, outcome, outcome.name());
+                                }
+                            });
+                            egui::ComboBox::from_id_source("effect").selected_text(action.effect.name()).show_ui(ui, |ui| {
+                                for effect in OutcomeEffect::ALL {
+                                    ui.selectable_value(&mut action.effect, effect, effect.name());
+                                }
+                            });
+                            if action.effect == OutcomeEffect::Swell {
+                                ui.add(egui::DragValue::new(&mut action.level).range(0..=100).suffix("%"));
+                                ui.label("for");
+                                ui.add(egui::DragValue::new(&mut action.seconds).range(1..=60).suffix(" s"));
+                            }
+                            if ui.button("🗑").on_hover_text("Delete").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                    });
+                }
+            });
+            if let Some(index) = removed {
+                self.outcome_actions.remove(index);
+            }
+            if ui.button("Add action").clicked() {
+                self.outcome_actions.push(OutcomeAction::default());
+            }
+        });
+
+        let mut script_open = self.script_open; // Se copia porque la ventana necesita acceso a todo self
+        egui::Window::new("Script")
+        .default_size(egui::vec2(560.0, 420.0))
+        .open(&mut script_open)
+        .show(ctx, |ui| {
+            ui.label("Write your own rules in a Rhai script: react to any line of the game log and decide what to do when the state changes. Saved changes are applied at once, even while the program is active.");
+            ui.add_space(4.0);
+            ui.checkbox(&mut self.script_enabled, "Enable script")
+                .on_hover_text("Applied the next time the program is activated");
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.save_script();
+                }
+                if ui.button("Check").clicked() {
+                    self.script_message = Some(scripting::check(&self.script_source).map(|_| "No errors found".to_owned()));
+                }
+                if ui.button("Revert").on_hover_text("Discard the unsaved changes").clicked() {
+                    self.open_script_editor();
+                }
+            });
+            match &self.script_message {
+                Some(Ok(message)) => { ui.label(message); }
+                Some(Err(err)) => { ui.colored_label(ui.visuals().error_fg_color, err); }
+                None => {}
+            }
+            if let Some(err) = scripting::last_error() {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Last error while running: {}", err));
+            }
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.add(egui::TextEdit::multiline(&mut self.script_source).code_editor().desired_rows(20).desired_width(f32::INFINITY));
+            });
         });
+        self.script_open = script_open;
+
+        let mut matchers_open = self.matchers_open; // Se copia porque la ventana necesita acceso a todo self
+        egui::Window::new("Log matchers")
+        .default_size(egui::vec2(560.0, 420.0))
+        .open(&mut matchers_open)
+        .show(ctx, |ui| {
+            ui.label("Rules that detect the events of the game in its log file. If an update of the game breaks the detection, you can fix it here without waiting for a new version: add a rule with the same name as a default rule to replace it, or a new rule to check it before the default ones.");
+            ui.add_space(4.0);
+
+            let defaults = matchers::defaults();
+            let mut overridden = None;
+            egui::CollapsingHeader::new("Default rules").show(ui, |ui| {
+                egui::Grid::new("default_matchers").striped(true).show(ui, |ui| {
+                    for rule in defaults.iter() {
+                        ui.label(&rule.name);
+                        ui.label(rule.event.name());
+                        ui.label(egui::RichText::new(matcher_summary(rule)).monospace().small());
+                        let is_overridden = self.matcher_rules.iter().any(|user| user.name == rule.name);
+                        if ui.add_enabled(!is_overridden, egui::Button::new("Override")).clicked() {
+                            overridden = Some(rule.clone());
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+            if let Some(rule) = overridden {
+                self.matcher_rules.push(rule);
+            }
+
+            ui.separator();
+            ui.label("Your rules");
+            let mut removed = None;
+            egui::ScrollArea::vertical().max_height(200.0).id_source("user_matchers").show(ui, |ui| {
+                for (index, rule) in self.matcher_rules.iter_mut().enumerate() {
+                    ui.push_id(index, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut rule.enabled, "");
+                            ui.add(egui::TextEdit::singleline(&mut rule.name).hint_text("Name").desired_width(140.0));
+                            egui::ComboBox::from_id_source("event").selected_text(rule.event.name()).show_ui(ui, |ui| {
+                                for event in MatchEvent::ALL {
+                                    ui.selectable_value(&mut rule.event, event, event.name());
+                                }
+                            });
+                            if defaults.iter().any(|default| default.name == rule.name) {
+                                ui.label(egui::RichText::new("Replaces a default rule").small().weak());
+                            }
+                            if ui.button("🗑").on_hover_text("Delete").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                        egui::Grid::new("conditions").show(ui, |ui| {
+                            for (label, value) in [("Category", &mut rule.category), ("Contains", &mut rule.contains), ("Excludes", &mut rule.excludes), ("Regex", &mut rule.regex)] {
+                                ui.label(label);
+                                optional_text_edit(ui, value);
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    ui.separator();
+                }
+            });
+            if let Some(index) = removed {
+                self.matcher_rules.remove(index);
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Add rule").clicked() {
+                    self.matcher_rules.push(MatcherRule { name: format!("Custom rule {}", self.matcher_rules.len() + 1), event: MatchEvent::MatchStarted, enabled: true, category: None, contains: None, excludes: None, regex: None });
+                }
+                if ui.button("Save").clicked() {
+                    self.matcher_message = Some(matchers::save_user(&self.matcher_rules).map(|_| "Log matchers saved".to_owned()));
+                    self.update_known_results();
+                }
+            });
+            if self.program_active {
+                ui.label(egui::RichText::new("Changes will be applied the next time the program is activated.").small().weak());
+            }
+            match &self.matcher_message {
+                Some(Ok(message)) => { ui.label(message); }
+                Some(Err(err)) => { ui.colored_label(ui.visuals().error_fg_color, err); }
+                None => {}
+            }
+
+            ui.separator();
+            ui.label("Test a line of the log against the current rules (including unsaved changes):");
+            ui.add(egui::TextEdit::singleline(&mut self.matcher_test_line).hint_text("[2024.08.31-18.32.40:120][12]LogShooterGameState: Match Ended ...").desired_width(f32::INFINITY).font(egui::TextStyle::Monospace));
+            if !self.matcher_test_line.trim().is_empty() {
+                match Matchers::new(matchers::merge(defaults, &self.matcher_rules)) {
+                    Ok(matchers) => match matchers.analyze(self.matcher_test_line.trim()) {
+                        Some((rule, event)) => ui.label(format!("Rule \"{}\" fires: {:?}", rule, event)),
+                        None => ui.label("No rule matches this line"),
+                    },
+                    Err(err) => ui.colored_label(ui.visuals().error_fg_color, err),
+                };
+            }
+        });
+        self.matchers_open = matchers_open;
+
+        let mut api_open = self.api_open; // Se copia porque la ventana necesita acceso a todo self
+        egui::Window::new("Remote API")
+        .default_size(egui::vec2(400.0, 180.0))
+        .open(&mut api_open)
+        .show(ctx, |ui| {
+            ui.label("A local server to control the program and read its state from other programs (stream overlays, Stream Deck...). It only accepts connections from this computer.");
+            ui.add_space(4.0);
+            let mut changed = ui.checkbox(&mut self.api_enabled, "Enable API").changed();
+            egui::Grid::new("api_settings").show(ui, |ui| {
+                ui.label("Port");
+                changed |= ui.add(egui::DragValue::new(&mut self.api_port).range(1024..=65535)).lost_focus();
+                ui.end_row();
+                ui.label("Token");
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.api_token.clone()).desired_width(220.0).font(egui::TextStyle::Monospace));
+                    if ui.button("📋").on_hover_text("Copy").clicked() {
+                        ctx.copy_text(self.api_token.clone());
+                    }
+                    if ui.button("Regenerate").clicked() {
+                        self.api_token = api::generate_token();
+                        changed = true;
+                    }
+                });
+                ui.end_row();
+            });
+            if changed {
+                self.restart_api(ctx);
+            }
+
+            if let Some(server) = &self.api_server {
+                ui.label(format!("Listening on http://127.0.0.1:{}", server.port));
+                ui.label(egui::RichText::new("Send the token in the \"Authorization: Bearer <token>\" header or in the \"token\" query parameter. Endpoints: GET /state, GET /events (WebSocket), POST /start, /stop, /profile and /volume.").small().weak());
+            }
+            if let Some(err) = &self.api_error {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+            }
+        });
+        self.api_open = api_open;
+
+        let mut settings_open = self.settings_open; // Se copia porque la ventana necesita acceso a todo self
+        egui::Window::new("Import/export settings")
+        .default_size(egui::vec2(420.0, 120.0))
+        .open(&mut settings_open)
+        .show(ctx, |ui| {
+            ui.label("Profiles, automatic profile rules, log and tray settings can be saved to a TOML file to share them or to use them on another computer.");
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.settings_path).desired_width(260.0));
+                if ui.button("Export").clicked() {
+                    let path = PathBuf::from(&self.settings_path);
+                    self.settings_result = Some(match settings::export(&self.settings(), &path) {
+                        Ok(_) => format!("Settings exported to {}", path.display()),
+                        Err(err) => err,
+                    });
+                }
+                if ui.add_enabled(!self.program_active, egui::Button::new("Import")).on_disabled_hover_text("Stop the program to import settings").clicked() {
+                    let path = PathBuf::from(&self.settings_path);
+                    self.settings_result = Some(match settings::import(&path) {
+                        Ok(settings) => {
+                            let message = format!("Imported {} profiles from {}", settings.profiles.len(), path.display());
+                            self.apply_settings(settings);
+                            message
+                        }
+                        Err(err) => err,
+                    });
+                }
+            });
+            if let Some(result) = &self.settings_result {
+                ui.label(result);
+            }
+        });
+        self.settings_open = settings_open;
+
+        egui::Window::new("Automatic profiles")
+        .default_size(egui::vec2(420.0, 200.0))
+        .open(&mut self.profile_rules_open)
+        .show(ctx, |ui| {
+            ui.label("When a match starts, the profile of the first matching rule is used. When it ends, the selected profile is used again.");
+            ui.label(egui::RichText::new("The game mode and the map are detected by the Riot Client API source. The game log only shows them with your own log matchers, see \"File\" > \"Log matchers\".").small().weak());
+            ui.add_space(4.0);
+
+            let mut removed_rule = None;
+            let mut moved_rule = None;
+            egui::Grid::new("profile_rules").striped(true).show(ui, |ui| {
+                ui.label("Game mode");
+                ui.label("Map");
+                ui.label("Profile");
+                ui.end_row();
+                for (index, rule) in self.profile_rules.iter_mut().enumerate() {
+                    egui::ComboBox::from_id_source(("rule_mode", index))
+                    .selected_text(rule.mode.map_or("Any", |mode| mode.name()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut rule.mode, None, "Any");
+                        for mode in GameMode::ALL {
+                            ui.selectable_value(&mut rule.mode, Some(mode), mode.name());
+                        }
+                    });
+                    egui::ComboBox::from_id_source(("rule_map", index))
+                    .selected_text(rule.map.as_deref().unwrap_or("Any"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut rule.map, None, "Any");
+                        for (_, map) in MAPS {
+                            ui.selectable_value(&mut rule.map, Some(map.to_owned()), map);
+                        }
+                    });
+                    egui::ComboBox::from_id_source(("rule_profile", index))
+                    .selected_text(rule.profile.clone())
+                    .show_ui(ui, |ui| {
+                        for profile in self.profiles.iter() {
+                            ui.selectable_value(&mut rule.profile, profile.name.clone(), profile.name.clone());
+                        }
+                    });
+                    if ui.add_enabled(index > 0, egui::Button::new("⏶")).on_hover_text("Move up").clicked() {
+                        moved_rule = Some(index);
+                    }
+                    if ui.button("🗑").on_hover_text("Remove").clicked() {
+                        removed_rule = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if let Some(index) = moved_rule {
+                self.profile_rules.swap(index - 1, index);
+            }
+            if let Some(index) = removed_rule {
+                self.profile_rules.remove(index);
+            }
+            if ui.button("Add rule").clicked() {
+                self.profile_rules.push(ProfileRule { mode: None, map: None, profile: self.profiles[self.selected_profile].name.clone() });
+            }
+            if self.program_active {
+                ui.label(egui::RichText::new("Changes will be applied the next time the program is activated.").small().weak());
+            }
+        });
+
+        egui::Window::new("Volume timeline")
+        .default_size(egui::vec2(500.0, 260.0))
+        .open(&mut self.timeline_open)
+        .show(ctx, |ui| {
+            self.timeline.show(ui);
+        });
+
+        egui::Window::new("Match history")
+        .default_size(egui::vec2(520.0, 320.0))
+        .open(&mut self.history_open)
+        .show(ctx, |ui| {
+            if self.history_records.is_empty() {
+                ui.label("No matches have been recorded yet. Matches are recorded while the program is active.");
+            }
+            else {
+                ui.strong("Daily totals");
+                egui::ScrollArea::vertical().id_source("history_daily_scroll").max_height(100.0).show(ui, |ui| {
+                    egui::Grid::new("history_daily").striped(true).show(ui, |ui| {
+                        ui.label("Date");
+                        ui.label("Matches");
+                        ui.label("Rounds");
+                        for state in 0..STATE_COUNT { ui.label(States::name(state as u8)); }
+                        ui.end_row();
+                        for totals in history::daily_totals(&self.history_records) {
+                            ui.label(totals.date.format("%Y-%m-%d").to_string());
+                            ui.label(totals.matches.to_string());
+                            ui.label(totals.rounds.to_string());
+                            for seconds in totals.state_seconds.iter() { ui.label(history::format_duration(*seconds)); }
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.separator();
+                ui.strong("Matches");
+                egui::ScrollArea::vertical().id_source("history_matches_scroll").max_height(120.0).show(ui, |ui| {
+                    egui::Grid::new("history_matches").striped(true).show(ui, |ui| {
+                        ui.label("Started");
+                        ui.label("Duration");
+                        ui.label("Rounds");
+                        ui.label("Winning team");
+                        ui.end_row();
+                        for record in self.history_records.iter().rev() {
+                            ui.label(history::local_time(record.started_at).format("%Y-%m-%d %H:%M").to_string());
+                            ui.label(history::format_duration((record.ended_at - record.started_at) as f64));
+                            ui.label(record.rounds_played.to_string());
+                            ui.label(match (&record.winning_team, record.completed) {
+                                (Some(team), _) => team.clone(),
+                                (None, true) => "-".to_owned(),
+                                (None, false) => "Not finished".to_owned(),
+                            });
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.history_export_path).desired_width(300.0));
+                if ui.add_enabled(!self.history_records.is_empty(), egui::Button::new("Export CSV")).clicked() {
+                    self.history_export_result = Some(match history::export_csv(&self.history_records, std::path::Path::new(&self.history_export_path)) {
+                        Ok(_) => format!("Exported {} matches", self.history_records.len()),
+                        Err(err) => format!("Couldn't export the history: {}", err),
+                    });
+                }
+            });
+            if let Some(result) = &self.history_export_result {
+                ui.label(result);
+            }
+        });
+
+        egui::Window::new("Log viewer")
+        .default_size(egui::vec2(500.0, 300.0))
+        .open(&mut self.log_viewer_open)
+        .show(ctx, |ui| {
+            let entries: Vec<logger::LogEntry> = logger::entries().into_iter()
+                .filter(|entry| entry.level <= self.log_level_filter)
+                .filter(|entry| self.log_search.is_empty() || entry.message.to_lowercase().contains(&self.log_search.to_lowercase()))
+                .collect();
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("log_level_filter")
+                .selected_text(self.log_level_filter.to_string())
+                .show_ui(ui, |ui| {
+                    for level in [log::LevelFilter::Error, log::LevelFilter::Warn, log::LevelFilter::Info, log::LevelFilter::Debug] {
+                        ui.selectable_value(&mut self.log_level_filter, level, level.to_string());
+                    }
+                });
+                ui.add(egui::TextEdit::singleline(&mut self.log_search).hint_text("Search").desired_width(150.0));
+                if ui.button("Copy to clipboard").clicked() {
+                    ctx.copy_text(entries.iter().map(|entry| entry.to_string()).collect::<Vec<String>>().join("\n"));
+                }
+                if ui.button("Clear").clicked() {
+                    logger::clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut self.log_to_file, "Write to log file").changed() {
+                    logger::set_file_logging(self.log_to_file);
+                }
+                let default_path = logger::default_file_path().map(|path| path.display().to_string()).unwrap_or_default();
+                if ui.add(egui::TextEdit::singleline(&mut self.log_path).hint_text(default_path).desired_width(ui.available_width())).lost_focus() {
+                    logger::set_file_path(custom_log_path(&self.log_path));
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::both()
+            .auto_shrink([false, false])
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for entry in entries.iter() {
+                    let color = match entry.level {
+                        log::Level::Error => ui.visuals().error_fg_color,
+                        log::Level::Warn => ui.visuals().warn_fg_color,
+                        _ => ui.visuals().text_color(),
+                    };
+                    ui.label(egui::RichText::new(entry.to_string()).monospace().color(color));
+                }
+            });
+        });
+
+        if let Some(journal) = self.pending_journal.clone() {
+            let mut restore_clicked = false;
+            let mut dismiss_clicked = false;
+            egui::Window::new("Restore volume")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "The program did not close correctly last time, so {} may have been left with a modified volume{}.",
+                    journal.process_name,
+                    if journal.paused { " and paused" } else { "" }
+                ));
+                ui.label(format!("Original volume: {:.0}%", journal.original_volume * 100.0));
+                if self.journal_restore_failed {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("{} is not running, open it and try again.", journal.process_name));
+                }
+                ui.add_space(8.0);
+                ui.checkbox(&mut self.auto_restore_volume, "Always restore automatically");
+                ui.horizontal(|ui| {
+                    restore_clicked = ui.button("Restore").clicked();
+                    dismiss_clicked = ui.button("Dismiss").clicked();
+                });
+            });
+
+            if restore_clicked {
+                // Se actualizan las sesiones de audio por si el proceso se abrió después que la aplicación
+                self.audio_controller = unsafe { AudioController::init(Some(CoinitMode::ApartmentThreaded)) };
+                self.process_list = get_process_list(&mut self.audio_controller);
+                self.selected_process_index = -1;
+                self.button_enabled = false;
+                self.button_label = "Select a process".to_owned();
+                self.restore_journal();
+            }
+            else if dismiss_clicked {
+                Journal::clear();
+                self.pending_journal = None;
+            }
+        }
 
         // Receptor de mensajes del hilo secundario
         if let Some(ref rx) = self.receiver {
-            if let Ok(_) = rx.try_recv() { // Si el hilo secundario terminó la simulación...
+            if let Ok(volume) = rx.try_recv() { // Si el hilo secundario terminó la simulación...
+                self.initial_process_volume = volume;
                 self.button_label = get_activate_button_label(self.simulation_checked);
                 self.button_enabled = true;
                 self.program_active = false;
-                unsafe { self.audio_controller.get_session_by_name(self.process_list[self.selected_process_index as usize].clone()).unwrap().setVolume(self.initial_process_volume); }
+                self.restore_process_volume();
                 sim_dialog.open();
             }
         }
@@ -281,31 +1616,67 @@ impl eframe::App for ValorantMusicControllerApp {
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
         if self.selected_process_index != -1 {
-            unsafe { // Si se cierra la app con el programa en ejecución, se para el programa y se restaura el volumen del proceso
-                if let Some(_) = self.program_thread {
-                    self.button_label = "Stopping program...".to_owned();
-                    self.button_enabled = false;
-                    self.program_thread.take().unwrap().stop().join().unwrap(); // Esperar a que el hilo termine
-                }
-                self.audio_controller.get_session_by_name(self.process_list[self.selected_process_index as usize].clone()).unwrap().setVolume(self.initial_process_volume);
+            // Si se cierra la app con el programa en ejecución, se para el programa y se restaura el volumen del proceso
+            if let Some(program_thread) = self.program_thread.take() {
+                self.button_label = "Stopping program...".to_owned();
+                self.button_enabled = false;
+                self.initial_process_volume = program_thread.stop().join().unwrap(); // Esperar a que el hilo termine
             }
+            self.restore_process_volume();
         }
     }
 }
 
+// Permite leer como Option un campo que se guardó sin serlo
+fn deserialize_some<'de, T: serde::Deserialize<'de>, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
+// Campo de texto para un valor opcional; vacío equivale a None
+fn optional_text_edit(ui: &mut egui::Ui, value: &mut Option<String>) {
+    let mut text = value.clone().unwrap_or_default();
+    if ui.add(egui::TextEdit::singleline(&mut text).hint_text("Any").desired_width(360.0).font(egui::TextStyle::Monospace)).changed() {
+        *value = if text.is_empty() { None } else { Some(text) };
+    }
+}
+
+// Condiciones de una regla del log en una línea, para la lista de reglas por defecto
+fn matcher_summary(rule: &MatcherRule) -> String {
+    [("category", &rule.category), ("contains", &rule.contains), ("excludes", &rule.excludes), ("regex", &rule.regex)].iter()
+        .filter_map(|(name, value)| value.as_ref().map(|value| format!("{}: {}", name, value)))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+// Ruta del archivo de log elegida por el usuario; vacía para usar la ruta por defecto
+fn custom_log_path(log_path: &str) -> Option<PathBuf> {
+    if log_path.trim().is_empty() { None } else { Some(PathBuf::from(log_path.trim())) }
+}
+
+// Ruta de un archivo de datos de la aplicación, en la misma carpeta que usa eframe para guardar la configuración
+fn data_path(file_name: &str) -> Option<PathBuf> {
+    eframe::storage_dir(APP_NAME).map(|dir| dir.join(file_name))
+}
+
+// Ruta por defecto para los archivos que exporta el usuario, en su carpeta personal
+fn default_export_path(file_name: &str) -> String {
+    let home = std::env::var("USERPROFILE").or_else(|_| std::env::var("HOME")).unwrap_or_default();
+    PathBuf::from(home).join(file_name).display().to_string()
+}
+
 fn get_process_list(controller: &mut AudioController) -> Vec<String> {
     unsafe {
         controller.GetSessions();
         controller.GetDefaultAudioEnpointVolumeControl();
         controller.GetAllProcessSessions();
-        return controller.get_all_session_names();
+        controller.get_all_session_names()
     }
 }
 
 // Retorna el nombre del botón cuando está disponible para ser activado; sin embargo, tiene dos posibles nombres, dependiendo de si se quiere simular o no
 fn get_activate_button_label(simulating: bool) -> String {
-    if simulating { return "Simulate a match".to_owned(); }
-    else { return "Activate program".to_owned(); }
+    if simulating { "Simulate a match".to_owned() }
+    else { "Activate program".to_owned() }
 }
 
 fn create_dialog(ctx: &egui::Context, id: String, title: String, body: String) -> egui_modal::Modal {
@@ -320,5 +1691,5 @@ fn create_dialog(ctx: &egui::Context, id: String, title: String, body: String) -
         });
     });
 
-    return dialog;
+    dialog
 }
\ No newline at end of file
@@ -0,0 +1,439 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, sleep, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use tungstenite::{Message, WebSocket};
+
+use super::commands::{self, ControllerCommand};
+use super::constants::*;
+use super::events::{self, ControllerEvent, ControllerStatus};
+
+pub const DEFAULT_PORT: u16 = 7878;
+// Tamaño máximo de la cabecera y del cuerpo de las peticiones
+const MAX_HEAD_SIZE: u64 = 16 * 1024;
+const MAX_BODY_SIZE: u64 = 16 * 1024;
+// Cada cuánto se comprueba si hay conexiones nuevas o si hay que parar el servidor
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+// Tiempo máximo para recibir una petición, para que un cliente lento no bloquee el servidor
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+// Tiempo máximo de cada lectura del WebSocket; es también el retraso máximo con el que se envían los eventos
+const WS_READ_TIMEOUT: Duration = Duration::from_millis(100);
+// Cada cuánto se envía un ping; si el cliente no ha respondido al anterior se cierra la conexión
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+// Peticiones que tiene que atender la aplicación porque crean o paran el controlador, o cambian el perfil elegido
+#[derive(Debug)]
+pub enum ApiRequest {
+    Start,
+    Stop,
+    SelectProfile(String),
+}
+
+// Servidor HTTP local (solo en 127.0.0.1) para overlays de streaming, Stream Deck, etc. Todas las peticiones
+// necesitan el token, en la cabecera "Authorization: Bearer <token>" o en el parámetro "?token=<token>".
+//
+// GET  /state    Estado actual del controlador
+// GET  /events   WebSocket por el que se envían todos los eventos del controlador en JSON
+// POST /start    Activa el programa con el proceso y el perfil elegidos
+// POST /stop     Para el programa y restaura el volumen
+// POST /profile  {"profile": "<nombre>"}
+// POST /volume   {"volume": 0-100} para fijar el volumen, {"volume": null} para volver al del perfil
+pub struct ApiServer {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    requests: mpsc::Receiver<ApiRequest>,
+    pub port: u16,
+}
+
+impl ApiServer {
+    // status: estado actual del controlador, que después se mantiene con sus eventos
+    pub fn start(port: u16, token: String, status: ControllerStatus, ctx: egui::Context) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|err| format!("Couldn't start the API on port {}: {}", port, err))?;
+        let port = listener.local_addr().map_or(port, |addr| addr.port());
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+
+        let mut handler = Handler { token, stop: stop.clone(), requests: tx, ctx, status, events: events::subscribe() };
+        let thread = thread::spawn(move || {
+            while !handler.stop.load(Ordering::Relaxed) {
+                handler.update_status();
+                match listener.accept() {
+                    Ok((stream, _)) => handler.handle(stream),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => sleep(ACCEPT_INTERVAL),
+                    Err(err) => {
+                        log::error!("API server error: {}", err);
+                        break;
+                    }
+                }
+            }
+        });
+
+        log::info!("API listening on http://127.0.0.1:{}", port);
+        Ok(Self { stop, thread: Some(thread), requests: rx, port })
+    }
+
+    pub fn requests(&self) -> Vec<ApiRequest> {
+        self.requests.try_iter().collect()
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        // Se espera a que termine el hilo para que el puerto quede libre si se vuelve a arrancar el servidor
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() { let _ = thread.join(); }
+        log::info!("API stopped");
+    }
+}
+
+// Token aleatorio para autenticar las peticiones, del generador aleatorio del sistema
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("The system random number generator is not available");
+    format!("{:032x}", u128::from_le_bytes(bytes))
+}
+
+struct Handler {
+    token: String,
+    stop: Arc<AtomicBool>,
+    requests: mpsc::Sender<ApiRequest>,
+    ctx: egui::Context, // Para que la interfaz atienda las peticiones aunque la ventana no se esté redibujando
+    status: ControllerStatus,
+    events: mpsc::Receiver<ControllerEvent>,
+}
+
+impl Handler {
+    // El estado se reconstruye con los eventos del controlador, igual que en la interfaz
+    fn update_status(&mut self) {
+        for event in self.events.try_iter() {
+            self.status.apply(&event);
+        }
+        self.status.recent_events.clear(); // No se usan y así no ocupan memoria
+    }
+
+    fn handle(&mut self, stream: TcpStream) {
+        let request = match Request::read(stream) {
+            Ok(request) => request,
+            Err(err) => return log::debug!("Invalid API request: {}", err),
+        };
+        if !self.authorized(&request) {
+            return respond(request, 401, json!({ "error": "Missing or invalid token" }));
+        }
+
+        let path = request.url.split('?').next().unwrap_or_default().to_owned();
+        log::debug!("API request: {} {}", request.method, path);
+        match (request.method.as_str(), path.as_str()) {
+            ("GET", "/state") => {
+                let body = self.state_json();
+                respond(request, 200, body)
+            }
+            ("GET", "/events") => self.upgrade(request),
+            ("POST", "/start") if self.status.active => respond(request, 409, json!({ "error": "The program is already active" })),
+            ("POST", "/start") => self.forward(request, ApiRequest::Start),
+            ("POST", "/stop") if !self.status.active => respond(request, 409, json!({ "error": "The program is not active" })),
+            ("POST", "/stop") => self.forward(request, ApiRequest::Stop),
+            ("POST", "/profile") => match read_json(&request).map(|body| body["profile"].as_str().map(str::to_owned)) {
+                Ok(Some(profile)) => self.forward(request, ApiRequest::SelectProfile(profile)),
+                Ok(None) => respond(request, 400, json!({ "error": "Expected {\"profile\": \"<name>\"}" })),
+                Err(err) => respond(request, 400, json!({ "error": err })),
+            },
+            ("POST", "/volume") => {
+                let level = match read_json(&request) {
+                    Ok(body) if body["volume"].is_null() => None,
+                    Ok(body) => match body["volume"].as_u64() {
+                        Some(volume) if volume <= 100 => Some(volume as u8),
+                        _ => return respond(request, 400, json!({ "error": "Expected {\"volume\": 0-100} or {\"volume\": null}" })),
+                    },
+                    Err(err) => return respond(request, 400, json!({ "error": err })),
+                };
+                if commands::send(ControllerCommand::OverrideVolume(level)) {
+                    respond(request, 202, json!({ "ok": true }))
+                }
+                else {
+                    respond(request, 409, json!({ "error": "The program is not active" }))
+                }
+            }
+            (_, "/state" | "/events" | "/start" | "/stop" | "/profile" | "/volume") => respond(request, 405, json!({ "error": "Method not allowed" })),
+            _ => respond(request, 404, json!({ "error": "Not found" })),
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let bearer = request.header("Authorization").and_then(|value| value.strip_prefix("Bearer "));
+        let query = request.url.split_once('?').and_then(|(_, query)| {
+            query.split('&').find_map(|param| param.strip_prefix("token="))
+        });
+        !self.token.is_empty() && [bearer, query].into_iter().flatten().any(|token| same_token(token, &self.token))
+    }
+
+    fn forward(&self, request: Request, api_request: ApiRequest) {
+        let _ = self.requests.send(api_request);
+        self.ctx.request_repaint();
+        respond(request, 202, json!({ "ok": true }))
+    }
+
+    fn state_json(&self) -> serde_json::Value {
+        let status = &self.status;
+        json!({
+            "active": status.active,
+            "state": States::key(status.state),
+            "state_name": States::name(status.state),
+            "round": status.round,
+            "score": status.score,
+            "side": status.round.and_then(|round| status.score.side(round, status.mode)),
+            "volume": (status.volume * 100.0).round(),
+            "target_volume": (status.target_volume * 100.0).round(),
+            "fade_progress": status.fade_progress,
+            "profile": status.profile,
+            "mode": status.mode,
+            "map": status.map,
+            "source": status.source,
+            "suspended": status.suspended,
+            "volume_override": status.volume_override,
+        })
+    }
+
+    // Convierte la petición en un WebSocket y envía por él los eventos del controlador desde un hilo propio
+    fn upgrade(&self, request: Request) {
+        let Some(key) = request.header("Sec-WebSocket-Key") else {
+            return respond(request, 400, json!({ "error": "Expected a WebSocket connection" }));
+        };
+
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            tungstenite::handshake::derive_accept_key(key.as_bytes())
+        );
+        let mut stream = request.stream;
+        // Las lecturas terminan pronto para enviar los eventos pendientes entre los mensajes del cliente
+        if stream.write_all(response.as_bytes()).and_then(|_| stream.set_read_timeout(Some(WS_READ_TIMEOUT))).is_err() { return; }
+        let events = events::subscribe();
+        let stop = self.stop.clone();
+
+        thread::spawn(move || {
+            let mut socket = WebSocket::from_raw_socket(stream, tungstenite::protocol::Role::Server, None);
+            log::debug!("API WebSocket client connected");
+            let mut last_ping = Instant::now();
+            let mut waiting_pong = false;
+            let mut closed = false; // El cliente ha cerrado la conexión
+
+            'connection: while !stop.load(Ordering::Relaxed) {
+                loop {
+                    let message = match events.try_recv() {
+                        Ok(event) => Message::Text(serde_json::to_string(&event).unwrap()),
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        Err(mpsc::TryRecvError::Disconnected) => break 'connection,
+                    };
+                    if socket.send(message).is_err() { break 'connection; }
+                }
+
+                // Mensajes del cliente. La respuesta a los Ping y al Close la añade tungstenite y se envía al leer o escribir.
+                match socket.read() {
+                    Ok(Message::Close(_)) => {
+                        let _ = socket.flush();
+                        closed = true;
+                        break;
+                    }
+                    Ok(Message::Pong(_)) => waiting_pong = false,
+                    Ok(_) => {}
+                    Err(tungstenite::Error::Io(err)) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                    Err(_) => break,
+                }
+
+                // Para detectar los clientes desconectados
+                if last_ping.elapsed() >= PING_INTERVAL {
+                    if waiting_pong {
+                        log::debug!("The API WebSocket client didn't answer the ping");
+                        break;
+                    }
+                    if socket.send(Message::Ping(Vec::new())).is_err() { break; }
+                    last_ping = Instant::now();
+                    waiting_pong = true;
+                }
+            }
+            if !closed { let _ = socket.close(None); }
+            log::debug!("API WebSocket client disconnected");
+        });
+    }
+}
+
+// Petición HTTP recibida. Se atiende una petición por conexión (se responde con "Connection: close").
+struct Request {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: String,
+    stream: TcpStream,
+}
+
+impl Request {
+    fn read(stream: TcpStream) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+        // En Windows la conexión hereda el modo no bloqueante del servidor
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new((&stream).take(MAX_HEAD_SIZE));
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(url), Some(version)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid("invalid request line"));
+        };
+        if !version.starts_with("HTTP/1.") { return Err(invalid("unsupported HTTP version")); }
+        let (method, url) = (method.to_owned(), url.to_owned());
+
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 { return Err(invalid("incomplete headers")); }
+            let line = line.trim_end();
+            if line.is_empty() { break; }
+            let (name, value) = line.split_once(':').ok_or_else(|| invalid("invalid header"))?;
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+
+        // Lo que ya se ha leído del cuerpo está en el buffer del lector, así que se sigue leyendo de él
+        let length = headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("Content-Length")).and_then(|(_, value)| value.parse::<u64>().ok()).unwrap_or(0);
+        let mut body = String::new();
+        reader.get_mut().set_limit(length.min(MAX_BODY_SIZE));
+        reader.read_to_string(&mut body)?;
+
+        Ok(Self { method, url, headers, body, stream })
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+// Comparación en tiempo constante, para que el tiempo de respuesta no indique cuántos caracteres del token coinciden
+fn same_token(candidate: &str, token: &str) -> bool {
+    let (candidate, token) = (candidate.as_bytes(), token.as_bytes());
+    let diff = candidate.iter().zip(token).fold(0, |diff, (a, b)| diff | (a ^ b));
+    candidate.len() == token.len() && std::hint::black_box(diff) == 0
+}
+
+fn read_json(request: &Request) -> Result<serde_json::Value, String> {
+    serde_json::from_str(&request.body).map_err(|err| format!("Invalid JSON: {}", err))
+}
+
+fn respond(mut request: Request, status: u16, body: serde_json::Value) {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "",
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, body.len(), body
+    );
+    if let Err(err) = request.stream.write_all(response.as_bytes()) {
+        log::debug!("Couldn't send the API response: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+    fn start_server(active: bool) -> ApiServer {
+        let status = ControllerStatus { active, ..Default::default() };
+        ApiServer::start(0, TOKEN.to_owned(), status, egui::Context::default()).unwrap()
+    }
+
+    // (código de estado, cuerpo) de la respuesta
+    fn request(server: &ApiServer, method: &str, url: &str, token: Option<&str>) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        let authorization = token.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: 127.0.0.1\r\n{}Content-Length: 0\r\n\r\n", method, url, authorization).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn tokens_are_random() {
+        let (a, b) = (generate_token(), generate_token());
+        assert_eq!(a.len(), 32);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn token_comparison() {
+        assert!(same_token(TOKEN, TOKEN));
+        assert!(!same_token("0123456789abcdef0123456789abcdee", TOKEN));
+        assert!(!same_token("0123456789abcdef", TOKEN));
+        assert!(!same_token("", TOKEN));
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let server = start_server(false);
+        assert_eq!(request(&server, "GET", "/state", None).0, 401);
+        assert_eq!(request(&server, "GET", "/state", Some("0123456789abcdef0123456789abcdee")).0, 401);
+        assert_eq!(request(&server, "GET", "/state", Some(TOKEN)).0, 200);
+        assert_eq!(request(&server, "GET", &format!("/state?token={}", TOKEN), None).0, 200);
+        assert_eq!(request(&server, "GET", "/nothing", Some(TOKEN)).0, 404);
+        assert_eq!(request(&server, "DELETE", "/state", Some(TOKEN)).0, 405);
+    }
+
+    #[test]
+    fn state_starts_from_the_current_status() {
+        let server = start_server(true);
+        let (status, body) = request(&server, "GET", "/state", Some(TOKEN));
+        assert_eq!(status, 200);
+        assert_eq!(body["active"], true);
+        assert_eq!(request(&server, "POST", "/start", Some(TOKEN)).0, 409);
+
+        let server = start_server(false);
+        assert_eq!(request(&server, "POST", "/stop", Some(TOKEN)).0, 409);
+        assert_eq!(request(&server, "POST", "/start", Some(TOKEN)).0, 202);
+        assert!(matches!(server.requests()[..], [ApiRequest::Start]));
+    }
+
+    #[test]
+    fn websocket_sends_events_and_closes() {
+        let server = start_server(false);
+        let stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        let url = format!("ws://127.0.0.1:{}/events?token={}", server.port, TOKEN);
+        let (mut socket, _) = tungstenite::client(url, stream).unwrap();
+        sleep(Duration::from_millis(200)); // A que el servidor se suscriba a los eventos
+
+        events::emit(ControllerEvent::ScriptEvent("api_test".to_owned()));
+        loop {
+            match socket.read().unwrap() {
+                Message::Text(text) if text.contains("api_test") => break,
+                _ => {}
+            }
+        }
+
+        // El servidor responde al Close del cliente
+        socket.close(None).unwrap();
+        let start = Instant::now();
+        loop {
+            match socket.read() {
+                Ok(_) => assert!(start.elapsed() < Duration::from_secs(2)),
+                Err(tungstenite::Error::ConnectionClosed) => break,
+                Err(err) => panic!("Unexpected error: {}", err),
+            }
+        }
+    }
+}
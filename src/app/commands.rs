@@ -0,0 +1,33 @@
+use std::sync::{mpsc, Mutex};
+
+// Órdenes que se envían al hilo del controlador desde fuera de la interfaz (ej. atajos de teclado globales)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControllerCommand {
+    ToggleSuspend, // Suspende o reanuda el control automático del volumen
+    CycleProfile,
+    SelectProfile(usize),
+    RestoreOriginal, // Restaura el volumen original y suspende el control automático
+    NudgeVolume(i8), // Sube o baja el volumen del estado actual, en puntos porcentuales
+    OverrideVolume(Option<u8>), // Fija el volumen en % para todos los estados; None para volver a usar el del perfil
+    SessionVolumeChanged(f32), // El usuario ha cambiado el volumen del proceso controlado
+    GameExited, // El juego se ha cerrado, así que ya no hay partida aunque el log no lo indique
+}
+
+// Canal del controlador en ejecución; None si no hay ninguno
+static CONTROLLER: Mutex<Option<mpsc::Sender<ControllerCommand>>> = Mutex::new(None);
+
+// Lo llama el controlador al empezar para recibir las órdenes que se envíen a partir de ahora
+pub fn connect() -> mpsc::Receiver<ControllerCommand> {
+    let (tx, rx) = mpsc::channel();
+    *CONTROLLER.lock().unwrap() = Some(tx);
+    rx
+}
+
+pub fn disconnect() {
+    *CONTROLLER.lock().unwrap() = None;
+}
+
+// Retorna false si no hay ningún controlador en ejecución que pueda recibir la orden
+pub fn send(command: ControllerCommand) -> bool {
+    CONTROLLER.lock().unwrap().as_ref().map_or(false, |tx| tx.send(command).is_ok())
+}
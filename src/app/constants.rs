@@ -1,6 +1,9 @@
 #![allow(non_snake_case)]
 
+use std::time::Duration;
+
 pub struct States;
+pub const STATE_COUNT: usize = 8;
 impl States {
     // Estado inicial del programa
     // y cuando finaliza una partida | [2024.08.31-17.27.38:105][866]LogShooterGameState: Match Ended: Completion State: ''. Winning Team: 'Blue' () |
@@ -13,4 +16,156 @@ impl States {
     pub const IN_GAME_PLAYING: u8 = 2;
     // Cuando un personaje pasa de vivo a muerto | [2024.08.31-18.51.39:595][870]LogAresMinimapComponent: Warning: Found Compute Position override on: MinimapRangeIndicator. Setting Position source to custom. Please change this in the asset. |
     // pub const IN_GAME_DEAD: u8 = 3;
-}
\ No newline at end of file
+
+    // Estados antes de la partida, según los cambios de estado del juego. Los valores siguen a los de los estados
+    // anteriores para que los perfiles guardados sigan siendo válidos.
+    // En el menú principal | LogGameFlowStateManager: Reconcile called with state: TransitionToMainMenu and new state: MainMenu. Changing state. |
+    pub const MENUS: u8 = 3;
+    // Buscando partida. Solo lo detecta la API del Riot Client ("partyState": "MATCHMAKING"); no se conoce ninguna línea del log que lo indique.
+    pub const IN_QUEUE: u8 = 4;
+    // Partida encontrada. Solo con la API del Riot Client ("partyState": "MATCHMADE_GAME_STARTING").
+    pub const MATCH_FOUND: u8 = 5;
+    // Selección de agente | LogGameFlowStateManager: Reconcile called with state: MainMenu and new state: Pregame. Changing state. |
+    pub const AGENT_SELECT: u8 = 6;
+    // Cargando el mapa | LogGameFlowStateManager: Reconcile called with state: Pregame and new state: TransitionToInGame. Changing state. |
+    pub const LOADING: u8 = 7;
+
+    // Todos los estados en el orden en el que se producen, para mostrarlos
+    pub const ALL: [u8; STATE_COUNT] = [
+        States::NOT_IN_GAME,
+        States::MENUS,
+        States::IN_QUEUE,
+        States::MATCH_FOUND,
+        States::AGENT_SELECT,
+        States::LOADING,
+        States::IN_GAME_PREPARING,
+        States::IN_GAME_PLAYING,
+    ];
+
+    pub fn name(state: u8) -> &'static str {
+        match state {
+            States::IN_GAME_PREPARING => "Buy phase",
+            States::IN_GAME_PLAYING => "Playing",
+            States::MENUS => "Menus",
+            States::IN_QUEUE => "In queue",
+            States::MATCH_FOUND => "Match found",
+            States::AGENT_SELECT => "Agent select",
+            States::LOADING => "Loading",
+            _ => "Not in game",
+        }
+    }
+
+    // Identificador del estado en el archivo de configuración
+    pub fn key(state: u8) -> &'static str {
+        match state {
+            States::IN_GAME_PREPARING => "buy_phase",
+            States::IN_GAME_PLAYING => "playing",
+            States::MENUS => "menus",
+            States::IN_QUEUE => "in_queue",
+            States::MATCH_FOUND => "match_found",
+            States::AGENT_SELECT => "agent_select",
+            States::LOADING => "loading",
+            _ => "not_in_game",
+        }
+    }
+
+    // Si el estado es de una partida en curso (los demás son fuera de partida o antes de empezarla)
+    pub fn in_match(state: u8) -> bool {
+        matches!(state, States::IN_GAME_PREPARING | States::IN_GAME_PLAYING)
+    }
+
+    pub fn is_pregame(state: u8) -> bool {
+        matches!(state, States::MENUS | States::IN_QUEUE | States::MATCH_FOUND | States::AGENT_SELECT | States::LOADING)
+    }
+}
+
+// Modo de juego de la partida, según la presencia del Riot Client o las reglas del log del usuario
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    Competitive,
+    Unrated,
+    Deathmatch,
+    SpikeRush,
+    Custom,
+    Range,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 6] = [GameMode::Competitive, GameMode::Unrated, GameMode::Deathmatch, GameMode::SpikeRush, GameMode::Custom, GameMode::Range];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Competitive => "Competitive",
+            GameMode::Unrated => "Unrated",
+            GameMode::Deathmatch => "Deathmatch",
+            GameMode::SpikeRush => "Spike Rush",
+            GameMode::Custom => "Custom",
+            GameMode::Range => "The Range",
+        }
+    }
+
+    // Identificador de la cola de matchmaking ("queueId" en la presencia del Riot Client o el grupo "queue" de una regla del log)
+    pub fn from_queue(queue: &str) -> Option<GameMode> {
+        match queue.to_lowercase().as_str() {
+            "competitive" => Some(GameMode::Competitive),
+            "unrated" => Some(GameMode::Unrated),
+            "deathmatch" => Some(GameMode::Deathmatch),
+            "spikerush" => Some(GameMode::SpikeRush),
+            "custom" => Some(GameMode::Custom),
+            _ => None,
+        }
+    }
+
+    // Tiempo aproximado desde que se detecta el inicio de la partida o el final de una ronda hasta que termina la fase de
+    // compra (incluye la pausa del final de ronda). None en los modos sin fase de compra.
+    pub fn buy_phase_end(mode: Option<GameMode>, first_round: bool) -> Option<Duration> {
+        let seconds = match (mode, first_round) {
+            (Some(GameMode::Deathmatch | GameMode::Range), _) => return None,
+            (Some(GameMode::SpikeRush), true) => 30,
+            (Some(GameMode::SpikeRush), false) => 22,
+            (_, true) => 45,
+            (_, false) => 37,
+        };
+        Some(Duration::from_secs(seconds))
+    }
+
+    // Rondas de cada mitad, antes del cambio de lado. None en los modos sin rondas por equipos.
+    pub fn rounds_per_half(mode: Option<GameMode>) -> Option<u32> {
+        match mode {
+            Some(GameMode::Deathmatch | GameMode::Range) => None,
+            Some(GameMode::SpikeRush) => Some(3),
+            _ => Some(12),
+        }
+    }
+
+    // Carpeta del modo de juego que se carga (el grupo "mode" de una regla del log), ej. /Game/GameModes/<carpeta>/...
+    // El modo normal (Bomb) se usa en competitivo, no competitivo y personalizadas, así que no indica el modo por sí solo
+    pub fn from_game_mode_path(folder: &str) -> Option<GameMode> {
+        match folder {
+            "Deathmatch" => Some(GameMode::Deathmatch),
+            "QuickBomb" => Some(GameMode::SpikeRush),
+            "ShootingRange" => Some(GameMode::Range),
+            _ => None,
+        }
+    }
+}
+
+// Nombre interno de cada mapa (el de /Game/Maps/<carpeta>/<nombre>) y su nombre en el juego
+pub const MAPS: [(&str, &str); 12] = [
+    ("Ascent", "Ascent"),
+    ("Bonsai", "Split"),
+    ("Canyon", "Fracture"),
+    ("Duality", "Bind"),
+    ("Foxtrot", "Breeze"),
+    ("Infinity", "Abyss"),
+    ("Jam", "Lotus"),
+    ("Juliett", "Sunset"),
+    ("Pitt", "Pearl"),
+    ("Port", "Icebox"),
+    ("Triad", "Haven"),
+    ("Range", "The Range"),
+];
+
+pub fn map_name(internal_name: &str) -> Option<&'static str> {
+    MAPS.iter().find(|(internal, _)| *internal == internal_name).map(|(_, name)| *name)
+}
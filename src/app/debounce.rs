@@ -0,0 +1,113 @@
+use std::time::{Duration, Instant};
+
+// Filtra los cambios de estado que llegan en ráfagas (ej. fin de ronda y fin de partida en la misma comprobación del log):
+// un estado detectado solo se aplica cuando se ha mantenido durante su tiempo mínimo, y si antes se detecta otro
+// estado, el pendiente se descarta. El momento actual se pasa como parámetro para no depender del reloj.
+#[derive(Default)]
+pub struct Debouncer {
+    pending: Option<(u8, Instant)>, // Estado detectado que todavía no se ha aplicado y desde cuándo
+}
+
+impl Debouncer {
+    // Registra el estado detectado en el log; current es el estado aplicado actualmente
+    pub fn push(&mut self, state: u8, current: u8, now: Instant) {
+        if state == current {
+            // Se ha vuelto al estado aplicado antes de que el otro se asentara, así que no se cambia nada
+            if let Some((pending, _)) = self.pending.take() {
+                log::debug!("Ignoring the transition to state {}, it didn't last enough", pending);
+            }
+        }
+        else if self.pending.map(|(pending, _)| pending) != Some(state) {
+            self.pending = Some((state, now));
+        }
+    }
+
+    // Retorna el estado pendiente si ya se ha mantenido durante su tiempo mínimo
+    pub fn poll(&mut self, now: Instant, min_dwell: impl Fn(u8) -> Duration) -> Option<u8> {
+        let (state, since) = self.pending?;
+        if now.saturating_duration_since(since) < min_dwell(state) { return None; }
+        self.pending = None;
+        Some(state)
+    }
+
+    // Momento en el que se asentará el estado pendiente, para comprobarlo sin esperar a la siguiente lectura del log
+    pub fn deadline(&self, min_dwell: impl Fn(u8) -> Duration) -> Option<Instant> {
+        self.pending.map(|(state, since)| since + min_dwell(state))
+    }
+
+    pub fn clear(&mut self) {
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::constants::States;
+    use super::*;
+
+    const NOT_IN_GAME: u8 = States::NOT_IN_GAME;
+    const PREPARING: u8 = States::IN_GAME_PREPARING;
+    const PLAYING: u8 = States::IN_GAME_PLAYING;
+
+    fn dwell(_: u8) -> Duration { Duration::from_millis(500) }
+
+    #[test]
+    fn burst_applies_only_the_last_state() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::default();
+        // Fin de ronda y fin de partida en la misma lectura del log
+        debouncer.push(PREPARING, PLAYING, start);
+        debouncer.push(NOT_IN_GAME, PLAYING, start + Duration::from_millis(10));
+
+        assert_eq!(debouncer.poll(start + Duration::from_millis(400), dwell), None);
+        assert_eq!(debouncer.poll(start + Duration::from_millis(510), dwell), Some(NOT_IN_GAME));
+        assert_eq!(debouncer.poll(start + Duration::from_secs(5), dwell), None);
+    }
+
+    #[test]
+    fn flap_back_to_current_drops_pending() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::default();
+        debouncer.push(PREPARING, PLAYING, start);
+        debouncer.push(PLAYING, PLAYING, start + Duration::from_millis(100));
+
+        assert_eq!(debouncer.deadline(dwell), None);
+        assert_eq!(debouncer.poll(start + Duration::from_secs(5), dwell), None);
+    }
+
+    #[test]
+    fn another_state_resets_the_timer() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::default();
+        debouncer.push(PREPARING, NOT_IN_GAME, start);
+        debouncer.push(PLAYING, NOT_IN_GAME, start + Duration::from_millis(300));
+
+        // El tiempo cuenta desde el segundo estado, no desde el primero
+        assert_eq!(debouncer.poll(start + Duration::from_millis(600), dwell), None);
+        assert_eq!(debouncer.poll(start + Duration::from_millis(800), dwell), Some(PLAYING));
+    }
+
+    #[test]
+    fn same_pending_state_keeps_the_timer() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::default();
+        debouncer.push(PREPARING, NOT_IN_GAME, start);
+        debouncer.push(PREPARING, NOT_IN_GAME, start + Duration::from_millis(300));
+
+        assert_eq!(debouncer.poll(start + Duration::from_millis(500), dwell), Some(PREPARING));
+    }
+
+    #[test]
+    fn deadline_is_since_plus_min_dwell() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::default();
+        assert_eq!(debouncer.deadline(dwell), None);
+
+        let since = start + Duration::from_millis(250);
+        debouncer.push(PLAYING, PREPARING, since);
+        let min_dwell = |state: u8| Duration::from_millis(100 * state as u64);
+        assert_eq!(debouncer.deadline(min_dwell), Some(since + Duration::from_millis(200)));
+        assert_eq!(debouncer.poll(since + Duration::from_millis(199), min_dwell), None);
+        assert_eq!(debouncer.poll(since + Duration::from_millis(200), min_dwell), Some(PLAYING));
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+use std::sync::{mpsc, Mutex};
+
+use super::constants::*;
+use super::outcomes::{Outcome, Score};
+
+// Número máximo de eventos que se guardan para mostrar en la lista de eventos recientes
+const MAX_RECENT_EVENTS: usize = 100;
+
+// Eventos que el hilo del controlador comunica a la interfaz (y a cualquier otro suscriptor)
+#[derive(Clone, Debug, serde::Serialize)]
+pub enum ControllerEvent {
+    Started { state: u8, simulation: bool },
+    StateChanged { prev: u8, next: u8 },
+    VolumeChanged { volume: f32, target: f32, progress: f32 }, // progress: progreso del fundido, de 0 a 1
+    RoundChanged(u32),
+    ScoreChanged(Score),
+    Outcome(Outcome), // Resultado de una ronda o de la partida para el jugador
+    SourceChanged(String), // Fuente de la que se obtiene el estado del juego
+    LineMatched(String),
+    MatchDetected { mode: Option<GameMode>, map: Option<String> },
+    ProfileChanged(String),
+    ProfileSelected(usize), // El perfil elegido por el usuario ha cambiado desde fuera de la interfaz (ej. atajo de teclado)
+    LevelChanged { profile: String, state: u8, level: u8 }, // Se ha cambiado el volumen de un estado de un perfil
+    Suspended(bool),
+    VolumeOverridden(Option<u8>),
+    ScriptEvent(String), // Evento propio enviado por el script del usuario
+    Stopped,
+}
+
+impl ControllerEvent {
+    // Texto que se muestra en la lista de eventos recientes; los pasos de cada fundido de volumen no se muestran
+    fn describe(&self) -> Option<String> {
+        match self {
+            ControllerEvent::Started { state, simulation: false } => Some(format!("Controller started ({})", States::name(*state))),
+            ControllerEvent::Started { simulation: true, .. } => Some("Simulation started".to_owned()),
+            ControllerEvent::StateChanged { prev, next } => Some(format!("{} -> {}", States::name(*prev), States::name(*next))),
+            ControllerEvent::VolumeChanged { target, progress, .. } if *progress >= 1.0 => Some(format!("Volume set to {:.0}%", target * 100.0)),
+            ControllerEvent::VolumeChanged { .. } => None,
+            ControllerEvent::RoundChanged(round) => Some(format!("Round {}", round)),
+            ControllerEvent::ScoreChanged(_) => None,
+            ControllerEvent::Outcome(outcome) => Some(outcome.name().to_owned()),
+            ControllerEvent::SourceChanged(source) => Some(format!("Reading the game state from: {}", source)),
+            ControllerEvent::LineMatched(_) => None,
+            ControllerEvent::MatchDetected { mode, map } => Some(format!(
+                "Match detected: {} on {}",
+                mode.map_or("Unknown mode", |mode| mode.name()),
+                map.as_deref().unwrap_or("unknown map")
+            )),
+            ControllerEvent::ProfileChanged(profile) => Some(format!("Profile: {}", profile)),
+            ControllerEvent::ProfileSelected(_) => None,
+            ControllerEvent::LevelChanged { profile, state, level } => Some(format!("{}: {} volume set to {}%", profile, States::name(*state), level)),
+            ControllerEvent::Suspended(true) => Some("Automatic control suspended".to_owned()),
+            ControllerEvent::Suspended(false) => Some("Automatic control resumed".to_owned()),
+            ControllerEvent::VolumeOverridden(Some(level)) => Some(format!("Volume overridden to {}%", level)),
+            ControllerEvent::VolumeOverridden(None) => Some("Volume override removed".to_owned()),
+            ControllerEvent::ScriptEvent(name) => Some(format!("Script event: {}", name)),
+            ControllerEvent::Stopped => Some("Controller stopped".to_owned()),
+        }
+    }
+}
+
+static SUBSCRIBERS: Mutex<Vec<mpsc::Sender<ControllerEvent>>> = Mutex::new(Vec::new());
+
+// Crea un nuevo canal por el que se recibirán todos los eventos que se emitan a partir de ahora
+pub fn subscribe() -> mpsc::Receiver<ControllerEvent> {
+    let (tx, rx) = mpsc::channel();
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
+pub fn emit(event: ControllerEvent) {
+    // Los suscriptores cuyo receptor se ha destruido se eliminan de la lista
+    SUBSCRIBERS.lock().unwrap().retain(|tx| tx.send(event.clone()).is_ok());
+}
+
+// Estado del controlador reconstruido a partir de los eventos recibidos
+#[derive(Default, Clone)]
+pub struct ControllerStatus {
+    pub active: bool,
+    pub state: u8,
+    pub volume: f32,
+    pub target_volume: f32,
+    pub fade_progress: f32,
+    pub round: Option<u32>,
+    pub score: Score,
+    pub last_line: Option<String>,
+    pub mode: Option<GameMode>,
+    pub map: Option<String>,
+    pub profile: Option<String>,
+    pub suspended: bool,
+    pub source: Option<String>,
+    pub volume_override: Option<u8>,
+    pub recent_events: VecDeque<(String, String)>, // (hora, descripción)
+}
+
+impl ControllerStatus {
+    pub fn apply(&mut self, event: &ControllerEvent) {
+        match event {
+            ControllerEvent::Started { state, .. } => {
+                self.active = true;
+                self.state = *state;
+                self.round = None;
+                self.score = Score::default();
+                self.last_line = None;
+                self.mode = None;
+                self.map = None;
+                self.profile = None;
+                self.suspended = false;
+                self.source = None;
+                self.volume_override = None;
+            }
+            ControllerEvent::StateChanged { next, .. } => {
+                self.state = *next;
+                if !States::in_match(*next) {
+                    self.round = None;
+                    self.mode = None;
+                    self.map = None;
+                }
+            }
+            ControllerEvent::VolumeChanged { volume, target, progress } => {
+                self.volume = *volume;
+                self.target_volume = *target;
+                self.fade_progress = *progress;
+            }
+            ControllerEvent::RoundChanged(round) => self.round = Some(*round),
+            ControllerEvent::ScoreChanged(score) => self.score = *score,
+            ControllerEvent::LineMatched(line) => self.last_line = Some(line.clone()),
+            ControllerEvent::MatchDetected { mode, map } => {
+                self.mode = *mode;
+                self.map = map.clone();
+            }
+            ControllerEvent::ProfileChanged(profile) => self.profile = Some(profile.clone()),
+            ControllerEvent::Suspended(suspended) => self.suspended = *suspended,
+            ControllerEvent::SourceChanged(source) => self.source = Some(source.clone()),
+            ControllerEvent::VolumeOverridden(level) => self.volume_override = *level,
+            ControllerEvent::ProfileSelected(_) | ControllerEvent::LevelChanged { .. } | ControllerEvent::ScriptEvent(_) | ControllerEvent::Outcome(_) => {}
+            ControllerEvent::Stopped => self.active = false,
+        }
+
+        if let Some(description) = event.describe() {
+            if self.recent_events.len() == MAX_RECENT_EVENTS { self.recent_events.pop_front(); }
+            self.recent_events.push_back((chrono::Local::now().format("%H:%M:%S").to_string(), description));
+        }
+    }
+}
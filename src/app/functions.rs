@@ -1,163 +1,785 @@
 #![allow(non_snake_case)]
 
-#[path = "./constants.rs"]
-mod constants;
-use constants::*;
+use super::constants::*;
 
 use stoppable_thread::SimpleAtomicBool;
 
-use std::path::Path;
-use std::io::{BufRead, BufReader, Seek};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::mpsc;
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use enigo::{Enigo, Key, Keyboard, Settings};
-use notify::{Watcher, RecursiveMode, Result, RecommendedWatcher, Config};
-use regex::Regex;
 use windows_volume_control::{AudioController, CoinitMode};
 
+use super::commands::{self, ControllerCommand};
+use super::debounce::Debouncer;
+use super::events::{self, ControllerEvent};
+use super::history::MatchRecorder;
+use super::journal::Journal;
+use super::matchers::{self, GameEvent, Matchers};
+use super::outcomes::{Outcome, OutcomeAction, OutcomeEffect, Score, Team};
+use super::profiles::{Profile, ProfileSet};
+use super::scripting::{Script, ScriptAction};
+use super::session_volume::SessionVolume;
+use super::sources::{AutomaticSource, GameStateSource, Lockfile, LogTail, RiotApi, SourceKind, SourceUpdate};
+
 static STATE: AtomicU8 = AtomicU8::new(States::NOT_IN_GAME);
-fn getState() -> u8 { return STATE.load(Ordering::Relaxed); }
+fn getState() -> u8 { STATE.load(Ordering::Relaxed) }
 fn setState(newState: u8) { STATE.store(newState, Ordering::Relaxed); }
 
-fn playOrPauseMedia() {
-    let mut enigo = Enigo::new(&Settings::default()).unwrap();
-    let _ = enigo.key(Key::MediaPlayPause, enigo::Direction::Click);
+// Si el log del juego lleva más tiempo sin modificarse, se considera que el juego no está abierto y no se analiza al empezar
+const BACKLOG_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+// Cada cuánto se consulta la fuente del estado del juego (ej. si el log ha cambiado), o antes si hay un cambio de estado pendiente de asentarse
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Tiempo que se espera al inicio de la ronda después del final previsto de la fase de compra antes de cancelar el fundido anticipado
+const EARLY_FADE_GRACE: Duration = Duration::from_secs(5);
+
+// Diferencia mínima entre el volumen de la sesión y el último aplicado para considerar que el usuario lo ha cambiado
+const VOLUME_TOLERANCE: f32 = 0.005;
+
+// Fundido al volumen de jugando que empieza antes de que termine la fase de compra
+struct EarlyFade {
+    start: Instant,
+    buy_phase_end: Instant,
+    started: bool,
 }
 
-fn updateVolume(prevState: u8, audio_controller: &mut AudioController, process_name: &mut String, volumes: [u8; 3]) {
-    let volume = volumes[getState() as usize] as f32 / 100.0;
-    let prevVolume = volumes[prevState as usize] as f32 / 100.0;
-    println!("Setting volume from {} to {}", prevVolume, volume);
+// Datos que necesita el hilo secundario para controlar el volumen del proceso seleccionado
+struct Controller {
+    audio_controller: AudioController,
+    session_volume: Option<SessionVolume>, // Para cambiar el volumen y recibir los cambios del usuario; None si no se ha encontrado la sesión
+    process_name: String,
+    profiles: ProfileSet,
+    profile: Profile, // Perfil en uso: el elegido por el usuario o el de la regla que coincide con la partida actual
+    base_volume: f32, // Volumen propio del usuario; en modo relativo, los volúmenes de cada estado son un porcentaje de este
+    applied_volume: f32, // Último volumen aplicado por el programa
+    paused: bool, // Si el programa ha pausado la reproducción
+    suspended: bool, // Si el control automático está suspendido; se siguen detectando los estados pero no se cambia el volumen
+    volume_override: Option<u8>, // Volumen fijo en % para todos los estados, en lugar del del perfil (ej. desde la API)
+    journal: Option<Journal>, // None hasta que se lee el volumen real del proceso
+    recorder: Option<MatchRecorder>, // Solo se registran las partidas reales, no las simulaciones
+    queue_mode: Option<GameMode>, // Modo según la cola de matchmaking
+    loaded_mode: Option<GameMode>, // Modo según el modo de juego cargado, tiene prioridad sobre el de la cola
+    map: Option<String>,
+    round: Option<u32>, // Ronda actual, empezando en 1
+    script: Option<Script>,
+    matchers: Matchers,
+    debouncer: Debouncer,
+    early_fade: Option<EarlyFade>,
+    score: Score,
+    outcome_actions: Vec<OutcomeAction>,
+    swell: Option<(u8, Instant)>, // Volumen temporal por el resultado de una ronda o partida y hasta cuándo se usa
+}
+
+impl Controller {
+    fn new(process_name: String, profiles: ProfileSet, script: Option<Script>, outcome_actions: Vec<OutcomeAction>) -> Self {
+        // No se pueden pasar entre hilos el controlador de audio por lo que se inicializa aquí
+        let mut audio_controller = unsafe { AudioController::init(Some(CoinitMode::ApartmentThreaded)) };
+        let original_volume = unsafe {
+            audio_controller.GetSessions();
+            audio_controller.GetDefaultAudioEnpointVolumeControl();
+            audio_controller.GetAllProcessSessions();
+            audio_controller.get_session_by_name(process_name.clone()).map(|session| session.getVolume())
+        };
+
+        // Se guarda el volumen original antes de modificarlo por si el programa no termina correctamente. Si el proceso
+        // aún no tiene sesión de audio no se conoce, así que el registro se crea cuando aparezca (readOriginalVolume).
+        let journal = original_volume.map(|volume| {
+            let journal = Journal::new(process_name.clone(), volume);
+            journal.save();
+            journal
+        });
+        let base_volume = original_volume.unwrap_or(1.0);
 
-    unsafe {
-        let selectedSession = audio_controller.get_session_by_name(process_name.clone());
-            
-        // Si se pretende pasar de un estado con volumen 0 a un estado con volumen mayor, se reanuda la música.
-        if prevVolume == 0.0 && volume > 0.0 { playOrPauseMedia(); }
-        
-        for i in 1..11 {
-            selectedSession.unwrap().setVolume(prevVolume + (volume - prevVolume) * (i as f32) / 10.0);
-            sleep(Duration::from_millis(100));
+        let session_volume = SessionVolume::open(&process_name);
+        if session_volume.is_none() { log::warn!("Volume changes made in {} won't be detected", process_name); }
+
+        let profile = profiles.selected().clone();
+        Self {
+            audio_controller,
+            session_volume,
+            process_name,
+            profiles,
+            profile,
+            base_volume,
+            applied_volume: base_volume,
+            paused: false,
+            suspended: false,
+            volume_override: None,
+            journal,
+            recorder: None,
+            queue_mode: None,
+            loaded_mode: None,
+            map: None,
+            round: None,
+            script,
+            matchers: Matchers::load(),
+            debouncer: Debouncer::default(),
+            early_fade: None,
+            score: Score::default(),
+            outcome_actions,
+            swell: None,
         }
+    }
 
-        // Si el volumen objetivo es 0 después de estar en un estado con volumen mayor que 0, se pausa la música.
-        if volume == 0.0 && prevVolume > 0.0 { playOrPauseMedia(); }
+    // Porcentaje de volumen de un estado
+    fn level(&self, state: u8) -> u8 {
+        self.volume_override
+            .or(self.swell.map(|(level, _)| level))
+            .or_else(|| self.profile.round_volume(state, self.round, &self.score, self.loaded_mode.or(self.queue_mode)))
+            .unwrap_or_else(|| self.profile.volume(state))
+    }
+
+    // Volumen que se debe aplicar en un estado dado
+    fn targetVolume(&self, state: u8) -> f32 {
+        let level = self.level(state) as f32 / 100.0;
+        if self.profile.relative_mode { self.base_volume * level } else { level }
+    }
+
+    // Si al empezar no se encontró la sesión de audio del proceso, se busca de nuevo para leer su volumen original y
+    // guardar el registro antes de modificarlo. Devuelve si ya se conoce el volumen original.
+    fn readOriginalVolume(&mut self) -> bool {
+        if self.journal.is_some() { return true; }
+
+        if self.session_volume.is_none() { self.session_volume = SessionVolume::open(&self.process_name); }
+        let Some(volume) = self.session_volume.as_ref().and_then(SessionVolume::volume) else { return false; };
+        log::info!("Found the audio session of {}, original volume: {:.0}%", self.process_name, volume * 100.0);
+
+        let journal = Journal::new(self.process_name.clone(), volume);
+        journal.save();
+        self.journal = Some(journal);
+        self.base_volume = volume;
+        self.applied_volume = volume;
+        true
     }
-}
 
-fn analyzeText(line: &str) -> u8 {
-    let re = Regex::new(r"^\[(?P<date>[^\]]+)\]\[(?P<code>[^\]]+)\](?P<name>[^\:]+):\s*(?P<text>.+)$").unwrap();
-    if let Some(captures) = re.captures(line) {
-        // let date = &captures["date"];
-        // let code = &captures["code"];
-        let name = &captures["name"];
-        let text = &captures["text"];
+    fn setVolume(&mut self, volume: f32) {
+        // Sin el volumen original no se modifica nada, ya que no se podría restaurar
+        if self.journal.is_none() { return; }
+        match &self.session_volume {
+            // Con su propio contexto para que el cambio no se notifique como uno del usuario
+            Some(session_volume) => session_volume.set(volume),
+            None => unsafe {
+                if let Some(session) = self.audio_controller.get_session_by_name(self.process_name.clone()) {
+                    session.setVolume(volume);
+                }
+            },
+        }
+        self.applied_volume = volume;
+    }
+
+    // El usuario ha cambiado el volumen de la sesión (notificado por SessionVolume). En modo relativo, el cambio pasa a
+    // ser el nuevo volumen base en lugar de sobrescribirse en el siguiente cambio de estado.
+    fn sessionVolumeChanged(&mut self, current: f32) {
+        if !self.profile.relative_mode || (current - self.applied_volume).abs() <= VOLUME_TOLERANCE { return; }
+        self.applied_volume = current;
+
+        // Con el control suspendido se aplica el volumen base sin modificar. En un estado al 0% (o si el usuario silencia
+        // la música) no se puede deducir el volumen base, así que se mantiene el anterior.
+        let level = if self.suspended { 1.0 } else { self.level(getState()) as f32 / 100.0 };
+        if level == 0.0 || current == 0.0 { return; }
+        self.base_volume = (current / level).min(1.0);
+        if let Some(journal) = self.journal.as_mut() {
+            journal.original_volume = self.base_volume;
+            journal.save();
+        }
+        log::info!("Volume changed by the user, new base volume: {:.0}%", self.base_volume * 100.0);
+    }
+
+    fn setProfile(&mut self, profile: Profile) {
+        if profile.name != self.profile.name {
+            log::info!("Switching to profile {}", profile.name);
+            events::emit(ControllerEvent::ProfileChanged(profile.name.clone()));
+        }
+        self.profile = profile;
+    }
 
-        // println!("Log date: {}", date);
-        // println!("Log code: {}", code);
-        // println!("Log name: {}", name);
-        // println!("Text: {}", text);
+    // Al empezar una partida se elige el perfil según el modo y el mapa detectados
+    fn matchStarted(&mut self) {
+        let mode = self.loaded_mode.or(self.queue_mode);
+        log::info!("Match detected: {:?} on {:?}", mode, self.map);
+        events::emit(ControllerEvent::MatchDetected { mode, map: self.map.clone() });
+        self.setProfile(self.profiles.for_match(mode, self.map.as_deref()).clone());
+        // El equipo del jugador se puede detectar antes de empezar (ej. durante la carga), así que se mantiene
+        self.setScore(Score { team: self.score.team, ..Score::default() });
+    }
+
+    // Al terminar la partida se vuelve al perfil elegido por el usuario
+    fn matchEnded(&mut self) {
+        self.setProfile(self.profiles.selected().clone());
+        self.loaded_mode = None;
+        self.map = None;
+        self.round = None;
+        self.setScore(Score::default());
+    }
+
+    // La partida termina sin su final normal (ej. al salir del juego); se guarda en el historial como incompleta
+    fn abortMatch(&mut self, reason: &str) {
+        log::warn!("{}, ending the match", reason);
+        if let Some(recorder) = self.recorder.as_mut() { recorder.stop(getState(), Instant::now()); }
+        self.early_fade = None;
+        self.matchEnded();
+    }
+
+    fn setScore(&mut self, score: Score) {
+        if score == self.score { return; }
+        self.score = score;
+        events::emit(ControllerEvent::ScoreChanged(score));
+    }
+
+    fn roundEnded(&mut self, winner: Option<Team>) {
+        let Some(winner) = winner else { return; };
+        let mut score = self.score;
+        let outcome = score.round_won(winner);
+        self.setScore(score);
+        log::info!("Round won by {}, score: {}", winner.name(), score.describe());
+        if let Some(outcome) = outcome { self.applyOutcome(outcome); }
+    }
 
-        // println!("{}", line);
+    // Aplica las acciones que el usuario ha asociado al resultado
+    fn applyOutcome(&mut self, outcome: Outcome) {
+        log::info!("{}", outcome.name());
+        events::emit(ControllerEvent::Outcome(outcome));
 
-        if name == "LogShooterGameState" {
-            if text.contains("Match Ended") {
-                println!("Match ended.");
-                return States::NOT_IN_GAME;
+        let mut update = false;
+        for action in self.outcome_actions.iter().filter(|action| action.enabled && action.outcome == outcome) {
+            match action.effect {
+                OutcomeEffect::Swell => {
+                    self.swell = Some((action.level.min(100), Instant::now() + Duration::from_secs(action.seconds as u64)));
+                    update = true;
+                }
+                OutcomeEffect::NextTrack if !self.suspended => nextTrack(),
+                OutcomeEffect::NextTrack => {}
             }
-            else if text.contains("AShooterGameState::OnRoundEnded") {
-                println!("Round ended.");
-                return States::IN_GAME_PREPARING;
+        }
+        if update && !self.suspended { updateVolume(self); }
+    }
+
+    // Vuelve al volumen del estado cuando termina el volumen temporal del resultado
+    fn checkSwell(&mut self) {
+        if self.swell.is_some_and(|(_, until)| Instant::now() >= until) {
+            self.swell = None;
+            if !self.suspended { updateVolume(self); }
+        }
+    }
+
+    fn setSuspended(&mut self, suspended: bool) {
+        if suspended == self.suspended { return; }
+        self.suspended = suspended;
+        log::info!("Automatic control {}", if suspended { "suspended" } else { "resumed" });
+        events::emit(ControllerEvent::Suspended(suspended));
+
+        if suspended {
+            // Se vuelve al volumen original del usuario y se reanuda la música si el programa la pausó
+            if self.paused { self.setPaused(false); }
+            if !self.readOriginalVolume() { return; }
+            self.setVolume(self.base_volume);
+            events::emit(ControllerEvent::VolumeChanged { volume: self.base_volume, target: self.base_volume, progress: 1.0 });
+        }
+        else {
+            updateVolume(self);
+        }
+    }
+
+    // Cambia el perfil elegido por el usuario mientras el programa está activo
+    fn selectProfile(&mut self, index: usize) {
+        self.profiles.selected = index;
+        events::emit(ControllerEvent::ProfileSelected(index));
+        self.setProfile(self.profiles.selected().clone());
+        if !self.suspended { updateVolume(self); }
+    }
+
+    fn handleCommand(&mut self, command: ControllerCommand) {
+        log::info!("Command received: {:?}", command);
+        match command {
+            ControllerCommand::ToggleSuspend => self.setSuspended(!self.suspended),
+            ControllerCommand::RestoreOriginal => self.setSuspended(true),
+            ControllerCommand::CycleProfile => self.selectProfile((self.profiles.selected + 1) % self.profiles.profiles.len()),
+            ControllerCommand::SelectProfile(index) if index < self.profiles.profiles.len() => self.selectProfile(index),
+            ControllerCommand::SelectProfile(_) => {}
+            ControllerCommand::OverrideVolume(level) => {
+                self.volume_override = level.map(|level| level.min(100));
+                events::emit(ControllerEvent::VolumeOverridden(self.volume_override));
+                if !self.suspended { updateVolume(self); }
             }
-            else if text.contains("Gameplay started at local time") && !text.contains("0.000000") {
-                println!("Round started.");
-                return States::IN_GAME_PLAYING;
+            ControllerCommand::SessionVolumeChanged(volume) => self.sessionVolumeChanged(volume),
+            ControllerCommand::GameExited => {
+                if States::in_match(getState()) { self.abortMatch("The game was closed"); }
+                self.debouncer.clear();
+                changeState(States::NOT_IN_GAME, self);
+            }
+            ControllerCommand::NudgeVolume(delta) => {
+                let state = getState();
+                let min = if state == States::NOT_IN_GAME { 1 } else { 0 };
+                let level = (self.profile.volume(state) as i16 + delta as i16).clamp(min, 100) as u8;
+                self.profile.volumes[state as usize] = level;
+                // Se guarda también en el perfil de la lista por si se vuelve a elegir durante esta ejecución
+                if let Some(profile) = self.profiles.profiles.iter_mut().find(|profile| profile.name == self.profile.name) {
+                    profile.volumes[state as usize] = level;
+                }
+                events::emit(ControllerEvent::LevelChanged { profile: self.profile.name.clone(), state, level });
+                if !self.suspended { updateVolume(self); }
             }
         }
-        else if name == "LogGameFlowStateManager" {
-            if text.contains("Reconcile called with state: TransitionToInGame and new state: InGame. Changing state") {
-                println!("Match started.");
-                return States::IN_GAME_PREPARING;
+    }
+
+    // Aplica las acciones pedidas por el script. Retorna true si ha cambiado el volumen y hay que volver a aplicarlo.
+    fn applyScriptActions(&mut self, actions: Vec<ScriptAction>) -> bool {
+        let mut update = false;
+        for action in actions {
+            log::debug!("Script action: {:?}", action);
+            match action {
+                ScriptAction::Emit(name) => {
+                    log::info!("Script event: {}", name);
+                    events::emit(ControllerEvent::ScriptEvent(name));
+                }
+                ScriptAction::SetState(state) => {
+                    self.debouncer.clear();
+                    changeState(state, self);
+                }
+                ScriptAction::SetVolume(level) if level != self.volume_override => {
+                    self.volume_override = level;
+                    events::emit(ControllerEvent::VolumeOverridden(level));
+                    update = !self.suspended;
+                }
+                ScriptAction::Pause if !self.paused && !self.suspended => self.setPaused(true),
+                ScriptAction::Resume if self.paused && !self.suspended => self.setPaused(false),
+                ScriptAction::SetVolume(_) | ScriptAction::Pause | ScriptAction::Resume | ScriptAction::Run(_) => {}
             }
         }
+        update
+    }
+
+    // Datos de la partida que recibe el script en on_state_change
+    fn scriptContext(&self, state: u8) -> rhai::Map {
+        let optional = |value: Option<rhai::Dynamic>| value.unwrap_or(rhai::Dynamic::UNIT);
+        let mut context = rhai::Map::new();
+        context.insert("round".into(), optional(self.round.map(|round| (round as rhai::INT).into())));
+        context.insert("map".into(), optional(self.map.clone().map(Into::into)));
+        context.insert("mode".into(), optional(self.loaded_mode.or(self.queue_mode).map(|mode| mode.name().into())));
+        context.insert("profile".into(), self.profile.name.clone().into());
+        context.insert("volume".into(), (self.level(state) as rhai::INT).into());
+        context.insert("suspended".into(), self.suspended.into());
+        context
+    }
+
+    // Programa el fundido anticipado a partir del evento que inicia la fase de compra (y cuándo se produjo, si se sabe), si el perfil lo usa
+    fn scheduleEarlyFade(&mut self, time: Option<chrono::DateTime<chrono::Utc>>, first_round: bool) {
+        self.early_fade = None;
+        if self.profile.early_fade_s == 0 { return; }
+        let Some(buy_phase) = GameMode::buy_phase_end(self.loaded_mode.or(self.queue_mode), first_round) else { return; };
+
+        // El log se lee con retraso, así que se descuenta el tiempo que ha pasado desde que se escribió la línea
+        let delay = time
+            .and_then(|time| (chrono::Utc::now() - time).to_std().ok())
+            .filter(|delay| *delay < buy_phase)
+            .unwrap_or_default();
+        let buy_phase_end = Instant::now() + buy_phase - delay;
+        let start = buy_phase_end.checked_sub(Duration::from_secs(self.profile.early_fade_s as u64)).unwrap_or(buy_phase_end);
+        log::debug!("Buy phase expected to end in {:.1}s", buy_phase_end.saturating_duration_since(Instant::now()).as_secs_f32());
+        self.early_fade = Some(EarlyFade { start, buy_phase_end, started: false });
+    }
+
+    // Próximo momento en el que hay que comprobar el fundido anticipado
+    fn earlyFadeDeadline(&self) -> Option<Instant> {
+        self.early_fade.as_ref().map(|fade| if fade.started { fade.buy_phase_end + EARLY_FADE_GRACE } else { fade.start })
+    }
+
+    fn checkEarlyFade(&mut self) {
+        let Some(fade) = self.early_fade.as_mut() else { return; };
+        let now = Instant::now();
+        if getState() != States::IN_GAME_PREPARING {
+            self.early_fade = None;
+        }
+        else if !fade.started && now >= fade.start {
+            fade.started = true;
+            log::info!("Fading to the playing volume before the buy phase ends");
+            if !self.suspended { updateVolumeFor(self, States::IN_GAME_PLAYING); }
+        }
+        else if fade.started && now >= fade.buy_phase_end + EARLY_FADE_GRACE {
+            // La ronda no ha empezado cuando se esperaba (ej. tiempo muerto), así que se vuelve al volumen de la fase de compra
+            log::info!("The round didn't start when expected, restoring the buy phase volume");
+            self.early_fade = None;
+            if !self.suspended { updateVolume(self); }
+        }
+    }
+
+    fn setPaused(&mut self, paused: bool) {
+        log::info!("{} media playback", if paused { "Pausing" } else { "Resuming" });
+        playOrPauseMedia();
+        self.paused = paused;
+        if let Some(journal) = self.journal.as_mut() {
+            journal.paused = paused;
+            journal.save();
+        }
+    }
+}
+
+pub fn playOrPauseMedia() {
+    let mut enigo = Enigo::new(&Settings::default()).unwrap();
+    let _ = enigo.key(Key::MediaPlayPause, enigo::Direction::Click);
+}
+
+fn nextTrack() {
+    log::info!("Skipping to the next track");
+    let mut enigo = Enigo::new(&Settings::default()).unwrap();
+    let _ = enigo.key(Key::MediaNextTrack, enigo::Direction::Click);
+}
+
+fn updateVolume(controller: &mut Controller) {
+    updateVolumeFor(controller, getState());
+}
+
+// Aplica el volumen de un estado, que normalmente es el actual
+fn updateVolumeFor(controller: &mut Controller, state: u8) {
+    if !controller.readOriginalVolume() { return; }
+    let volume = controller.targetVolume(state);
+    let prevVolume = controller.applied_volume;
+    log::debug!("Setting volume from {:.2} to {:.2}", prevVolume, volume);
+
+    // La pausa depende del porcentaje configurado en el estado, no del volumen aplicado (en modo relativo el volumen base podría ser 0)
+    let level = controller.level(state);
+
+    // Si se pretende pasar a un estado con volumen mayor que 0 con la música pausada, se reanuda la música.
+    if controller.paused && level > 0 { controller.setPaused(false); }
+
+    // El fundido se hace en pasos de 100 ms. Si ya se está en el volumen objetivo (ej. tras un fundido anticipado) no se espera.
+    let steps = if volume == prevVolume { 0 } else { (controller.profile.fade_duration_ms / 100).max(1) };
+    for i in 1..=steps {
+        let progress = i as f32 / steps as f32;
+        controller.setVolume(prevVolume + (volume - prevVolume) * progress);
+        events::emit(ControllerEvent::VolumeChanged { volume: controller.applied_volume, target: volume, progress });
+        sleep(Duration::from_millis((controller.profile.fade_duration_ms / steps) as u64));
+    }
+    if steps == 0 {
+        events::emit(ControllerEvent::VolumeChanged { volume, target: volume, progress: 1.0 });
+    }
+
+    // Si el volumen objetivo es 0, se pausa la música.
+    if level == 0 && !controller.paused && controller.profile.pause_on_zero { controller.setPaused(true); }
+}
+
+// Estado al que se pasa cuando se produce un evento, si el evento cambia el estado. Los estados previos a la partida
+// no se aplican durante una partida (ej. el estado del grupo se sigue escribiendo), salvo volver a los menús.
+fn stateForEvent(event: &GameEvent, current: u8) -> Option<u8> {
+    match event {
+        // Al volver a una partida en curso (ej. al reconectar) se vuelve a detectar el inicio, pero se sigue en el estado actual
+        GameEvent::MatchStarted => Some(States::IN_GAME_PREPARING).filter(|_| !States::in_match(current)),
+        GameEvent::RoundEnded(..) => Some(States::IN_GAME_PREPARING),
+        GameEvent::MatchAborted => Some(States::NOT_IN_GAME),
+        GameEvent::RoundStarted => Some(States::IN_GAME_PLAYING),
+        GameEvent::MatchEnded(_) => Some(States::NOT_IN_GAME),
+        GameEvent::Pregame(States::MENUS) => Some(States::MENUS),
+        GameEvent::Pregame(state) => Some(*state).filter(|_| !States::in_match(current)),
+        GameEvent::QueueDetected(_) | GameEvent::GameModeLoaded(_) | GameEvent::MapLoaded(_) | GameEvent::PlayerTeam(_) => None,
     }
-    
-    return getState(); // No se ha producido ningún cambio, se mantiene el estado actual.
 }
 
-fn watchFile(should_stop: &SimpleAtomicBool, audio_controller: &mut AudioController, process_name: &mut String, volumes: [u8; 3]) -> Result<()> {
-    let binding = std::env::var("LOCALAPPDATA").unwrap() + "\\VALORANT\\Saved\\Logs\\ShooterGame.log";
+fn analyzeText(matchers: &Matchers, line: &str) -> Option<GameEvent> {
+    let (rule, event) = matchers.analyze(line)?;
+    logEvent(&event);
+    log::debug!("Matched by rule \"{}\"", rule);
+    Some(event)
+}
+
+fn logEvent(event: &GameEvent) {
+    match event {
+        GameEvent::MatchStarted => log::info!("Match started."),
+        GameEvent::RoundEnded(..) => log::info!("Round ended."),
+        GameEvent::RoundStarted => log::info!("Round started."),
+        GameEvent::MatchEnded(_) => log::info!("Match ended."),
+        GameEvent::QueueDetected(mode) => log::debug!("Queue detected: {}", mode.name()),
+        GameEvent::GameModeLoaded(mode) => log::debug!("Game mode loaded: {:?}", mode),
+        GameEvent::MapLoaded(map) => log::debug!("Map loaded: {}", map),
+        GameEvent::Pregame(state) => log::info!("{}.", States::name(*state)),
+        GameEvent::PlayerTeam(team) => log::debug!("Player team: {}", team.name()),
+        GameEvent::MatchAborted => log::info!("Match aborted."),
+    }
+}
+
+// Cambia al estado dado y aplica su volumen si es distinto al actual
+fn changeState(newState: u8, controller: &mut Controller) {
+    if newState != getState() {
+        let prevState = getState();
+        setState(newState);
+        controller.early_fade = None;
+        log::info!("State changed: {} -> {}", States::name(prevState), States::name(newState));
+        if let Some(recorder) = controller.recorder.as_mut() { recorder.state_changed(prevState, newState, Instant::now()); }
+        events::emit(ControllerEvent::StateChanged { prev: prevState, next: newState });
+
+        // El volumen que pida el script se aplica junto con el del nuevo estado y el resto de acciones, después
+        let context = controller.scriptContext(newState);
+        let actions = controller.script.as_mut().map(|script| script.on_state_change(prevState, newState, context)).unwrap_or_default();
+        let (volumeActions, otherActions): (Vec<_>, Vec<_>) = actions.into_iter()
+            .filter(|action| match action {
+                ScriptAction::SetState(_) => { log::warn!("Script: set_state() can only be used in on_log_line"); false }
+                _ => true,
+            })
+            .partition(|action| matches!(action, ScriptAction::SetVolume(_)));
+        controller.applyScriptActions(volumeActions);
+
+        if !controller.suspended { updateVolume(controller); }
+        if controller.applyScriptActions(otherActions) { updateVolume(controller); }
+    }
+}
+
+fn logPath() -> PathBuf {
+    let binding = std::env::var("LOCALAPPDATA").unwrap_or_default() + "\\VALORANT\\Saved\\Logs\\ShooterGame.log";
     // let binding = "D:\\Users\\Saulete\\Downloads\\test.txt";
-    let path = Path::new(&binding);
-    let mut f = std::fs::File::open(&path)?;
-    let mut pos = std::fs::metadata(&path)?.len();
+    PathBuf::from(binding)
+}
 
-    let (tx, _) = std::sync::mpsc::channel();
-    let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
-    watcher.watch(path.as_ref(), RecursiveMode::NonRecursive)?;
+// Estado de la partida deducido de las líneas del log que ya se habían escrito
+struct Backlog {
+    pos: u64, // Posición del archivo hasta la que se ha leído
+    state: u8,
+    round: Option<u32>,
+    score: Score,
+}
+
+// El log se crea de nuevo cada vez que se abre el juego, así que contiene toda la sesión actual. Se analiza entero, sin
+// aplicar nada, para saber si el programa se ha activado en mitad de una partida.
+fn readBacklog(controller: &mut Controller, path: &Path) -> Backlog {
+    let Ok(contents) = std::fs::read(path) else { return Backlog { pos: 0, state: States::NOT_IN_GAME, round: None, score: Score::default() }; };
+    let mut backlog = Backlog { pos: contents.len() as u64, state: States::NOT_IN_GAME, round: None, score: Score::default() };
+
+    let age = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok().and_then(|modified| modified.elapsed().ok());
+    if age.map_or(true, |age| age > BACKLOG_MAX_AGE) {
+        log::debug!("The game log is not being written, the game is not running");
+        return backlog;
+    }
+
+    for line in String::from_utf8_lossy(&contents).lines() {
+        let Some((_, event)) = controller.matchers.analyze(line) else { continue; };
+        match &event {
+            GameEvent::MatchStarted if States::in_match(backlog.state) => {}
+            GameEvent::MatchStarted => {
+                backlog.round = Some(1);
+                backlog.score = Score { team: backlog.score.team, ..Score::default() };
+            }
+            GameEvent::RoundEnded(round, winner) => {
+                backlog.round = Some(round + 2);
+                if let Some(winner) = winner { backlog.score.round_won(*winner); }
+            }
+            GameEvent::PlayerTeam(team) => backlog.score.team = Some(*team),
+            GameEvent::MatchEnded(_) | GameEvent::MatchAborted | GameEvent::Pregame(States::MENUS) => {
+                backlog.round = None;
+                backlog.score = Score::default();
+                controller.loaded_mode = None;
+                controller.map = None;
+            }
+            GameEvent::QueueDetected(mode) => controller.queue_mode = Some(*mode),
+            GameEvent::GameModeLoaded(mode) => controller.loaded_mode = *mode,
+            GameEvent::MapLoaded(map) => controller.map = Some(map.clone()),
+            GameEvent::RoundStarted | GameEvent::Pregame(_) => {}
+        }
+        if let Some(state) = stateForEvent(&event, backlog.state) { backlog.state = state; }
+    }
+    backlog
+}
+
+// Línea del log: se pasa al script y se analiza con las reglas
+fn handleLine(controller: &mut Controller, text: String) {
+    let actions = controller.script.as_mut().map(|script| script.on_log_line(&text)).unwrap_or_default();
+    if controller.applyScriptActions(actions) { updateVolume(controller); }
+
+    if let Some(event) = analyzeText(&controller.matchers, &text) {
+        events::emit(ControllerEvent::LineMatched(text.clone()));
+        handleEvent(controller, &event, matchers::log_time(&text));
+    }
+}
+
+// Aplica un evento del juego; time es cuándo se produjo, si se sabe
+fn handleEvent(controller: &mut Controller, event: &GameEvent, time: Option<chrono::DateTime<chrono::Utc>>) {
+    match event {
+        GameEvent::MatchStarted if States::in_match(getState()) => log::info!("Back to the match in progress"),
+        GameEvent::MatchStarted => {
+            controller.matchStarted();
+            controller.round = Some(1);
+            events::emit(ControllerEvent::RoundChanged(1));
+            controller.scheduleEarlyFade(time, true);
+        }
+        GameEvent::RoundEnded(round, winner) => {
+            controller.round = Some(round + 2); // Siguiente ronda, empezando en 1
+            events::emit(ControllerEvent::RoundChanged(round + 2));
+            if let Some(recorder) = controller.recorder.as_mut() { recorder.round_ended(*round); }
+            controller.roundEnded(*winner);
+            controller.scheduleEarlyFade(time, false);
+        }
+        GameEvent::MatchEnded(team) => {
+            if let Some(recorder) = controller.recorder.as_mut() { recorder.match_ended(team.clone()); }
+            let outcome = team.as_deref().and_then(Team::from_log).and_then(|winner| controller.score.match_outcome(winner));
+            if let Some(outcome) = outcome { controller.applyOutcome(outcome); }
+            controller.matchEnded();
+        }
+        GameEvent::QueueDetected(mode) => controller.queue_mode = Some(*mode),
+        GameEvent::GameModeLoaded(mode) => controller.loaded_mode = *mode,
+        GameEvent::MapLoaded(map) => controller.map = Some(map.clone()),
+        GameEvent::PlayerTeam(team) => controller.setScore(Score { team: Some(*team), ..controller.score }),
+        // Si se vuelve a los menús sin que se haya detectado el final (ej. al salir de la partida), se da por terminada
+        GameEvent::Pregame(States::MENUS) if States::in_match(getState()) => controller.abortMatch("Back to the menus during a match"),
+        GameEvent::MatchAborted => controller.abortMatch("The match ended without a result"),
+        GameEvent::RoundStarted | GameEvent::Pregame(_) => {}
+    }
+    if let Some(state) = stateForEvent(event, getState()) { controller.debouncer.push(state, getState(), Instant::now()); }
+}
+
+fn useSource(name: &str) {
+    log::info!("Reading the game state from: {}", name);
+    events::emit(ControllerEvent::SourceChanged(name.to_owned()));
+}
+
+// Consulta periódicamente la fuente del estado del juego y aplica lo que detecte. En modo automático la fuente cambia
+// entre la API y el log según estén disponibles.
+fn watchSource(should_stop: &SimpleAtomicBool, controller: &mut Controller, commands: &mpsc::Receiver<ControllerCommand>, mut source: Box<dyn GameStateSource>) {
+    let mut name = source.name();
+    useSource(name);
+    let mut failing = false; // Para avisar solo una vez mientras la fuente no está disponible
 
-    // No se puede comprobar actualizaciones en el archivo por culpa de cómo funciona Windows; por tanto,
-    // se comprueba cada X segundos si el archivo ha sido modificado.
     while !should_stop.get() {
-        if std::fs::metadata(&path)?.len() != pos {
-            f.seek(std::io::SeekFrom::Start(pos))?;
-            pos = std::fs::metadata(&path)?.len();
-
-            let reader = BufReader::new(&f);
-            for line in reader.lines() {
-                let text = line.unwrap();
-                if !text.is_empty() {
-                    let newState = analyzeText(&text);
-                    if newState != getState() { // Si el estado ha cambiado, se actualiza.
-                        let prevState = getState();
-                        setState(newState);
-                        updateVolume(prevState, audio_controller, process_name, volumes);
+        if let Some(script) = controller.script.as_mut() { script.reload_if_changed(); }
+
+        let result = source.poll();
+        if source.name() != name {
+            name = source.name();
+            useSource(name);
+            failing = false;
+        }
+        match result {
+            Ok(updates) => {
+                if failing { log::info!("{} is available again", source.name()); }
+                failing = false;
+                for update in updates {
+                    match update {
+                        SourceUpdate::Line(text) => handleLine(controller, text),
+                        SourceUpdate::Event(event) => {
+                            logEvent(&event);
+                            handleEvent(controller, &event, None);
+                        }
                     }
                 }
             }
+            Err(err) if !failing => {
+                log::warn!("{}", err);
+                failing = true;
+            }
+            Err(_) => {}
         }
 
-        //println!("Current state: {}", getState());
-        sleep(Duration::from_secs(1));
+        // Solo se aplica el último estado de cada ráfaga, cuando se ha mantenido el tiempo mínimo del perfil
+        if let Some(state) = controller.debouncer.poll(Instant::now(), |state| controller.profile.min_dwell(state)) {
+            changeState(state, controller);
+        }
+        controller.checkEarlyFade();
+        controller.checkSwell();
+
+        // Mientras se espera a la siguiente comprobación se atienden las órdenes recibidas (ej. atajos de teclado)
+        // Si hay un estado pendiente o un fundido anticipado se comprueba en cuanto toque
+        let deadline = [
+            controller.debouncer.deadline(|state| controller.profile.min_dwell(state)),
+            controller.earlyFadeDeadline(),
+            controller.swell.map(|(_, until)| until),
+        ].into_iter().flatten().min();
+        let timeout = deadline
+            .map_or(POLL_INTERVAL, |deadline| deadline.saturating_duration_since(Instant::now()).clamp(MIN_POLL_INTERVAL, POLL_INTERVAL));
+        match commands.recv_timeout(timeout) {
+            Ok(command) => controller.handleCommand(command),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => sleep(timeout),
+        }
     }
+}
+
+// Retorna el volumen que se debe restaurar al terminar (el volumen base del usuario, que puede haber cambiado en modo relativo)
+pub fn main_function(should_stop: &SimpleAtomicBool, process_name: String, profiles: ProfileSet, script: Option<Script>, outcome_actions: Vec<OutcomeAction>, source_kind: SourceKind) -> f32 {
+    let mut controller = Controller::new(process_name, profiles, script, outcome_actions);
+    controller.recorder = Some(MatchRecorder::default());
+
+    setState(States::NOT_IN_GAME);
+    log::info!("Controller started for {}", controller.process_name);
+    events::emit(ControllerEvent::Started { state: States::NOT_IN_GAME, simulation: false });
+
+    let path = logPath();
+    let lockfile = Lockfile::path();
+    let source: Box<dyn GameStateSource> = match source_kind {
+        SourceKind::Log => Box::new(readLogSource(&mut controller, &path)),
+        // Si el Riot Client no está abierto se sigue intentando conectar en cada consulta. El estado actual se obtiene
+        // en la primera consulta que responda.
+        SourceKind::RiotApi => {
+            updateVolume(&mut controller);
+            Box::new(RiotApi::new(&lockfile))
+        }
+        SourceKind::Automatic => match RiotApi::connect(&lockfile) {
+            Ok(api) => {
+                updateVolume(&mut controller);
+                Box::new(AutomaticSource::new(&lockfile, Some(api), LogTail::open_at_end(&path)))
+            }
+            Err(err) => {
+                log::info!("{}, using the game log", err);
+                let log = readLogSource(&mut controller, &path);
+                Box::new(AutomaticSource::new(&lockfile, None, log))
+            }
+        },
+    };
 
-    Ok(())
+    let commands = commands::connect();
+    watchSource(should_stop, &mut controller, &commands, source);
+    commands::disconnect();
+    if let Some(recorder) = controller.recorder.as_mut() { recorder.stop(getState(), Instant::now()); }
+    log::info!("Controller stopped");
+    events::emit(ControllerEvent::Stopped);
+
+    controller.base_volume
 }
 
-pub fn main_function(should_stop: &SimpleAtomicBool, process_name: String, volumes: [u8; 3]) {
-    // No se pueden pasar entre hilos el controlador de audio por lo que se inicializa aquí
-    let mut audio_controller = unsafe { AudioController::init(Some(CoinitMode::ApartmentThreaded)) };
-    unsafe {
-        audio_controller.GetSessions();
-        audio_controller.GetDefaultAudioEnpointVolumeControl();
-        audio_controller.GetAllProcessSessions();
+// Aplica lo que ya se había escrito en el log y lo abre para leer lo que se escriba a partir de ahora
+fn readLogSource(controller: &mut Controller, path: &Path) -> LogTail {
+    // Si se activa en mitad de una partida se pasa directamente al estado actual en lugar de aplicar el volumen de fuera de partida
+    let backlog = readBacklog(controller, path);
+    if !States::in_match(backlog.state) {
+        // Se establece el volumen inicial, del estado previo a la partida si se ha detectado alguno
+        if backlog.state == States::NOT_IN_GAME { updateVolume(controller); }
+        else { changeState(backlog.state, controller); }
+    }
+    else {
+        log::info!("Match in progress: {}, round {}", States::name(backlog.state), backlog.round.map_or("unknown".to_owned(), |round| round.to_string()));
+        controller.matchStarted();
+        controller.round = backlog.round;
+        if let Some(round) = backlog.round { events::emit(ControllerEvent::RoundChanged(round)); }
+        controller.setScore(backlog.score);
+        changeState(backlog.state, controller);
     }
 
-    updateVolume(States::NOT_IN_GAME, &mut audio_controller, &mut process_name.clone(), volumes); // Se establece el volumen inicial
-    watchFile(should_stop, &mut audio_controller, &mut process_name.clone(), volumes).unwrap();
+    // Si el juego todavía no ha creado el log se espera a que aparezca
+    LogTail::open(path, backlog.pos)
 }
 
-// Simulamos una partida de prueba: Entro en una partida, empiezo a jugar, muero, me reviven, empieza una nueva ronda y termina la partida por surrender.
-pub fn simulate_match(process_name: String, volumes: [u8; 3]) {
-    let mut audio_controller = unsafe { AudioController::init(Some(CoinitMode::ApartmentThreaded)) };
-    unsafe {
-        audio_controller.GetSessions();
-        audio_controller.GetDefaultAudioEnpointVolumeControl();
-        audio_controller.GetAllProcessSessions();
-    }
+// Simulamos una partida de prueba: Estoy en los menús, elijo agente, carga la partida, empiezo a jugar, empieza una nueva ronda y termina la partida por surrender.
+pub fn simulate_match(process_name: String, profiles: ProfileSet, script: Option<Script>, outcome_actions: Vec<OutcomeAction>) -> f32 {
+    let mut controller = Controller::new(process_name, profiles, script, outcome_actions);
 
     let simulationStates = [
+        States::MENUS,
+        States::AGENT_SELECT,
+        States::LOADING,
         States::IN_GAME_PREPARING,
         States::IN_GAME_PLAYING,
         States::IN_GAME_PREPARING,
         States::NOT_IN_GAME
     ];
 
+    setState(States::NOT_IN_GAME);
+    log::info!("Simulating a match for {}", controller.process_name);
+    events::emit(ControllerEvent::Started { state: States::NOT_IN_GAME, simulation: true });
     for state in simulationStates.iter() {
-        let prevState = getState();
-        setState(*state);
-        updateVolume(prevState, &mut audio_controller, &mut process_name.clone(), volumes);
+        changeState(*state, &mut controller);
         sleep(Duration::from_secs(1));
     }
-}
\ No newline at end of file
+    events::emit(ControllerEvent::Stopped);
+
+    controller.base_volume
+}
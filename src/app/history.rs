@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::{Local, NaiveDate, TimeZone};
+
+use super::constants::*;
+
+const HISTORY_FILE: &str = "match_history.jsonl";
+
+// Partida registrada en el historial; el archivo de historial tiene una partida en formato JSON por línea
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct MatchRecord {
+    pub started_at: i64, // Segundos desde la época UNIX
+    pub ended_at: i64,
+    pub rounds_played: u32,
+    pub state_seconds: Vec<f64>, // Tiempo pasado en cada estado, indexado por el valor del estado
+    pub winning_team: Option<String>,
+    pub completed: bool, // false si el programa se paró antes de que terminara la partida
+}
+
+impl Default for MatchRecord {
+    fn default() -> Self {
+        let now = Local::now().timestamp();
+        Self {
+            started_at: now,
+            ended_at: now,
+            rounds_played: 0,
+            state_seconds: vec![0.0; STATE_COUNT],
+            winning_team: None,
+            completed: false,
+        }
+    }
+}
+
+impl MatchRecord {
+    fn seconds_in(&self, state: u8) -> f64 {
+        self.state_seconds.get(state as usize).copied().unwrap_or(0.0)
+    }
+}
+
+// Construye el registro de la partida en curso a partir de los cambios de estado del controlador
+pub struct MatchRecorder {
+    current: Option<MatchRecord>,
+    state_since: Option<Instant>,
+    path: Option<PathBuf>, // Archivo del historial en el que se guardan las partidas
+}
+
+impl Default for MatchRecorder {
+    fn default() -> Self {
+        Self { current: None, state_since: None, path: super::data_path(HISTORY_FILE) }
+    }
+}
+
+impl MatchRecorder {
+    pub fn state_changed(&mut self, prev: u8, next: u8, now: Instant) {
+        if let (Some(record), Some(since)) = (self.current.as_mut(), self.state_since) {
+            record.state_seconds[prev as usize] += now.saturating_duration_since(since).as_secs_f64();
+        }
+        self.state_since = Some(now);
+
+        if !States::in_match(prev) && States::in_match(next) && self.current.is_none() {
+            self.current = Some(MatchRecord::default());
+        }
+        else if States::in_match(prev) && !States::in_match(next) {
+            self.finish(true);
+        }
+    }
+
+    pub fn round_ended(&mut self, round: u32) {
+        if let Some(record) = self.current.as_mut() {
+            record.rounds_played = record.rounds_played.max(round + 1);
+        }
+    }
+
+    pub fn match_ended(&mut self, winning_team: Option<String>) {
+        if let Some(record) = self.current.as_mut() {
+            record.winning_team = winning_team;
+        }
+    }
+
+    // Guarda la partida en curso; se llama al terminar la partida o al parar el programa en mitad de una
+    pub fn finish(&mut self, completed: bool) {
+        let Some(mut record) = self.current.take() else { return; };
+        record.ended_at = Local::now().timestamp();
+        record.completed = completed;
+        if let Some(path) = &self.path { append(&record, path); }
+    }
+
+    // Al parar el programa se suma el tiempo del estado actual y se guarda la partida en curso, si la hay
+    pub fn stop(&mut self, state: u8, now: Instant) {
+        if let (Some(record), Some(since)) = (self.current.as_mut(), self.state_since.take()) {
+            record.state_seconds[state as usize] += now.saturating_duration_since(since).as_secs_f64();
+        }
+        self.finish(false);
+    }
+}
+
+fn append(record: &MatchRecord, path: &Path) {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    let result = OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| writeln!(file, "{}", serde_json::to_string(record).unwrap()));
+    match result {
+        Ok(_) => log::info!("Match saved to the history ({} rounds)", record.rounds_played),
+        Err(err) => log::error!("Couldn't save the match to the history: {}", err),
+    }
+}
+
+pub fn load() -> Vec<MatchRecord> {
+    super::data_path(HISTORY_FILE).map(|path| load_from(&path)).unwrap_or_default()
+}
+
+fn load_from(path: &Path) -> Vec<MatchRecord> {
+    let Ok(contents) = std::fs::read_to_string(path) else { return Vec::new(); };
+
+    contents.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<MatchRecord>(line) {
+            Ok(mut record) => {
+                record.state_seconds.resize(STATE_COUNT, 0.0);
+                Some(record)
+            }
+            Err(err) => {
+                log::warn!("Ignoring invalid match history entry: {}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+// Totales de todas las partidas jugadas en un día
+pub struct DailyTotals {
+    pub date: NaiveDate,
+    pub matches: u32,
+    pub rounds: u32,
+    pub state_seconds: Vec<f64>,
+}
+
+pub fn daily_totals(records: &[MatchRecord]) -> Vec<DailyTotals> {
+    let mut days: BTreeMap<NaiveDate, DailyTotals> = BTreeMap::new();
+    for record in records {
+        let date = local_time(record.started_at).date_naive();
+        let totals = days.entry(date).or_insert_with(|| DailyTotals { date, matches: 0, rounds: 0, state_seconds: vec![0.0; STATE_COUNT] });
+        totals.matches += 1;
+        totals.rounds += record.rounds_played;
+        for state in 0..STATE_COUNT {
+            totals.state_seconds[state] += record.seconds_in(state as u8);
+        }
+    }
+
+    days.into_values().rev().collect() // Los días más recientes primero
+}
+
+pub fn export_csv(records: &[MatchRecord], path: &Path) -> std::io::Result<()> {
+    let mut csv = String::from("started_at,ended_at,duration_seconds,rounds_played,winning_team,completed");
+    for state in 0..STATE_COUNT {
+        csv += &format!(",seconds_{}", States::name(state as u8).to_lowercase().replace(' ', "_"));
+    }
+    csv.push('\n');
+
+    for record in records {
+        csv += &format!(
+            "{},{},{},{},{},{}",
+            local_time(record.started_at).format("%Y-%m-%d %H:%M:%S"),
+            local_time(record.ended_at).format("%Y-%m-%d %H:%M:%S"),
+            record.ended_at - record.started_at,
+            record.rounds_played,
+            record.winning_team.clone().unwrap_or_default().replace(',', " "),
+            record.completed
+        );
+        for state in 0..STATE_COUNT {
+            csv += &format!(",{:.0}", record.seconds_in(state as u8));
+        }
+        csv.push('\n');
+    }
+
+    std::fs::write(path, csv)
+}
+
+pub fn local_time(timestamp: i64) -> chrono::DateTime<Local> {
+    Local.timestamp_opt(timestamp, 0).single().unwrap_or_else(Local::now)
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    if seconds >= 3600 { format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60) }
+    else { format!("{}m {:02}s", seconds / 60, seconds % 60) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vmc-test-{}-{}", std::process::id(), name))
+    }
+
+    fn recorder(path: &Path) -> MatchRecorder {
+        let _ = std::fs::remove_file(path);
+        MatchRecorder { path: Some(path.to_owned()), ..Default::default() }
+    }
+
+    fn timestamp(day: u32, hour: u32) -> i64 {
+        Local.with_ymd_and_hms(2024, 8, day, hour, 0, 0).unwrap().timestamp()
+    }
+
+    fn record(started_at: i64, rounds_played: u32, playing: f64) -> MatchRecord {
+        let mut state_seconds = vec![0.0; STATE_COUNT];
+        state_seconds[States::IN_GAME_PLAYING as usize] = playing;
+        MatchRecord { started_at, ended_at: started_at + 1800, rounds_played, state_seconds, winning_team: Some("Blue".to_owned()), completed: true }
+    }
+
+    #[test]
+    fn completed_match() {
+        let path = temp_path("history-completed.jsonl");
+        let mut recorder = recorder(&path);
+        let start = Instant::now();
+        let at = |seconds: u64| start + Duration::from_secs(seconds);
+
+        // El tiempo fuera de la partida no se cuenta
+        recorder.state_changed(States::NOT_IN_GAME, States::MENUS, at(0));
+        recorder.state_changed(States::MENUS, States::IN_GAME_PREPARING, at(20));
+        recorder.state_changed(States::IN_GAME_PREPARING, States::IN_GAME_PLAYING, at(65));
+        recorder.round_ended(0);
+        recorder.state_changed(States::IN_GAME_PLAYING, States::IN_GAME_PREPARING, at(125));
+        recorder.state_changed(States::IN_GAME_PREPARING, States::IN_GAME_PLAYING, at(155));
+        recorder.round_ended(1);
+        recorder.match_ended(Some("Red".to_owned()));
+        recorder.state_changed(States::IN_GAME_PLAYING, States::NOT_IN_GAME, at(235));
+        // Después de guardarla no queda ninguna partida en curso
+        recorder.stop(States::NOT_IN_GAME, at(300));
+
+        let records = load_from(&path);
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert!(record.completed);
+        assert_eq!(record.rounds_played, 2);
+        assert_eq!(record.winning_team.as_deref(), Some("Red"));
+        assert_eq!(record.seconds_in(States::IN_GAME_PREPARING), 75.0);
+        assert_eq!(record.seconds_in(States::IN_GAME_PLAYING), 140.0);
+        assert_eq!(record.seconds_in(States::MENUS), 0.0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn stop_during_a_match() {
+        let path = temp_path("history-stopped.jsonl");
+        let mut recorder = recorder(&path);
+        let start = Instant::now();
+        recorder.state_changed(States::LOADING, States::IN_GAME_PREPARING, start);
+        recorder.state_changed(States::IN_GAME_PREPARING, States::IN_GAME_PLAYING, start + Duration::from_secs(30));
+        recorder.stop(States::IN_GAME_PLAYING, start + Duration::from_secs(50));
+
+        let records = load_from(&path);
+        assert_eq!(records.len(), 1);
+        assert!(!records[0].completed);
+        assert_eq!(records[0].winning_team, None);
+        assert_eq!(records[0].seconds_in(States::IN_GAME_PLAYING), 20.0);
+
+        // Sin una partida en curso no se guarda nada
+        recorder.stop(States::NOT_IN_GAME, start + Duration::from_secs(60));
+        assert_eq!(load_from(&path).len(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn totals_by_day() {
+        let records = [record(timestamp(30, 22), 20, 600.0), record(timestamp(31, 10), 13, 400.0), record(timestamp(31, 18), 24, 700.0)];
+        let totals = daily_totals(&records);
+
+        // Los días más recientes primero
+        assert_eq!(totals.iter().map(|totals| totals.date.to_string()).collect::<Vec<_>>(), ["2024-08-31", "2024-08-30"]);
+        assert_eq!((totals[0].matches, totals[0].rounds), (2, 37));
+        assert_eq!(totals[0].state_seconds[States::IN_GAME_PLAYING as usize], 1100.0);
+        assert_eq!((totals[1].matches, totals[1].rounds), (1, 20));
+    }
+
+    #[test]
+    fn csv() {
+        let path = temp_path("history.csv");
+        let mut unfinished = record(timestamp(31, 18), 7, 330.4);
+        unfinished.winning_team = Some("Red, Blue".to_owned());
+        unfinished.completed = false;
+        export_csv(&[record(timestamp(31, 10), 13, 400.0), unfinished], &path).unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("started_at,ended_at,duration_seconds,rounds_played,winning_team,completed,seconds_not_in_game,seconds_buy_phase,seconds_playing,"));
+        assert_eq!(lines[0].split(',').count(), 6 + STATE_COUNT);
+        assert!(lines[1].starts_with("2024-08-31 10:00:00,2024-08-31 10:30:00,1800,13,Blue,true,0,0,400,"));
+        // Las comas del equipo no pueden romper las columnas
+        assert!(lines[2].starts_with("2024-08-31 18:00:00,2024-08-31 18:30:00,1800,7,Red  Blue,false,0,0,330,"));
+        assert_eq!(lines[2].split(',').count(), 6 + STATE_COUNT);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,320 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::constants::*;
+use super::events::{self, ControllerEvent, ControllerStatus};
+use super::outcomes::Outcome;
+
+// Número de comandos que se están ejecutando, para respetar el límite de comandos simultáneos
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+// Momento en el que se ejecuta un comando
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum HookTrigger {
+    StateChanged, // Cualquier cambio de estado, o solo los que coincidan con from/to
+    MatchStarted,
+    MatchEnded,
+    RoundStarted,
+    Outcome(Outcome), // Ronda o partida ganada o perdida
+}
+
+impl HookTrigger {
+    pub const ALL: [HookTrigger; 8] = [
+        HookTrigger::StateChanged, HookTrigger::MatchStarted, HookTrigger::MatchEnded, HookTrigger::RoundStarted,
+        HookTrigger::Outcome(Outcome::RoundWon), HookTrigger::Outcome(Outcome::RoundLost), HookTrigger::Outcome(Outcome::MatchWon), HookTrigger::Outcome(Outcome::MatchLost),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookTrigger::StateChanged => "State changed",
+            HookTrigger::MatchStarted => "Match started",
+            HookTrigger::MatchEnded => "Match ended",
+            HookTrigger::RoundStarted => "Round started",
+            HookTrigger::Outcome(outcome) => outcome.name(),
+        }
+    }
+
+    // Valor de VMC_EVENT
+    fn key(&self) -> &'static str {
+        match self {
+            HookTrigger::StateChanged => "state_changed",
+            HookTrigger::MatchStarted => "match_started",
+            HookTrigger::MatchEnded => "match_ended",
+            HookTrigger::RoundStarted => "round_started",
+            HookTrigger::Outcome(Outcome::RoundWon) => "round_won",
+            HookTrigger::Outcome(Outcome::RoundLost) => "round_lost",
+            HookTrigger::Outcome(Outcome::MatchWon) => "match_won",
+            HookTrigger::Outcome(Outcome::MatchLost) => "match_lost",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct Hook {
+    pub enabled: bool,
+    pub trigger: HookTrigger,
+    pub from: Option<u8>, // Solo para StateChanged; None para cualquier estado
+    pub to: Option<u8>,
+    pub command: String,
+}
+
+impl Default for Hook {
+    fn default() -> Self {
+        Self { enabled: true, trigger: HookTrigger::StateChanged, from: None, to: None, command: String::new() }
+    }
+}
+
+impl Hook {
+    fn matches(&self, trigger: HookTrigger, prev: u8, next: u8) -> bool {
+        self.enabled && !self.command.trim().is_empty() && self.trigger == trigger
+            && (trigger != HookTrigger::StateChanged || (self.from.map_or(true, |from| from == prev) && self.to.map_or(true, |to| to == next)))
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct HookConfig {
+    pub enabled: bool,
+    pub timeout_secs: u32, // Los comandos que tardan más se terminan
+    pub max_concurrent: usize, // Si ya hay tantos comandos en ejecución, los nuevos no se ejecutan
+    pub hooks: Vec<Hook>,
+}
+
+impl Default for HookConfig {
+    fn default() -> Self {
+        Self { enabled: false, timeout_secs: 10, max_concurrent: 4, hooks: Vec::new() }
+    }
+}
+
+// Ejecuta los comandos configurados cuando el controlador emite los eventos correspondientes. El hilo se para al
+// destruir el HookRunner.
+pub struct HookRunner {
+    stop: Arc<AtomicBool>,
+}
+
+impl HookRunner {
+    // Retorna None si los comandos están desactivados o no hay ninguno
+    pub fn start(config: HookConfig) -> Option<Self> {
+        if !config.enabled || !config.hooks.iter().any(|hook| hook.enabled) { return None; }
+
+        let enabled_hooks = config.hooks.iter().filter(|hook| hook.enabled).count();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let events = events::subscribe();
+        thread::spawn(move || {
+            let mut status = ControllerStatus::default();
+            while !thread_stop.load(Ordering::Relaxed) {
+                let Ok(event) = events.recv_timeout(Duration::from_secs(1)) else { continue; };
+
+                // Las variables se calculan antes de actualizar el estado para que al acabar la partida sigan
+                // teniendo el mapa, el modo y la ronda
+                let mut triggers = Vec::new();
+                match event {
+                    ControllerEvent::StateChanged { prev, next } => {
+                        triggers.push((HookTrigger::StateChanged, prev, next, status.round));
+                        if !States::in_match(prev) && States::in_match(next) { triggers.push((HookTrigger::MatchStarted, prev, next, status.round)); }
+                        if States::in_match(prev) && !States::in_match(next) { triggers.push((HookTrigger::MatchEnded, prev, next, status.round)); }
+                    }
+                    ControllerEvent::RoundChanged(round) => triggers.push((HookTrigger::RoundStarted, status.state, status.state, Some(round))),
+                    ControllerEvent::Outcome(outcome) => triggers.push((HookTrigger::Outcome(outcome), status.state, status.state, status.round)),
+                    _ => {}
+                }
+                for (trigger, prev, next, round) in triggers {
+                    let env = environment(trigger.key(), &status, prev, next, round);
+                    for hook in config.hooks.iter().filter(|hook| hook.matches(trigger, prev, next)) {
+                        run(hook.command.clone(), env.clone(), &config);
+                    }
+                }
+
+                status.apply(&event);
+                status.recent_events.clear();
+            }
+        });
+
+        log::info!("Running commands on {} hooks", enabled_hooks);
+        Some(Self { stop })
+    }
+}
+
+impl Drop for HookRunner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// Ejecuta un comando con el estado actual, como si se hubiera producido su evento
+pub fn test(hook: &Hook, config: &HookConfig, status: &ControllerStatus) {
+    let env = environment("test", status, hook.from.unwrap_or(status.state), hook.to.unwrap_or(status.state), status.round);
+    run(hook.command.clone(), env, config);
+}
+
+// Variables de entorno que describen el evento
+fn environment(event: &str, status: &ControllerStatus, prev: u8, next: u8, round: Option<u32>) -> Vec<(&'static str, String)> {
+    vec![
+        ("VMC_EVENT", event.to_owned()),
+        ("VMC_PREV_STATE", States::key(prev).to_owned()),
+        ("VMC_STATE", States::key(next).to_owned()),
+        ("VMC_STATE_NAME", States::name(next).to_owned()),
+        ("VMC_ROUND", round.map(|round| round.to_string()).unwrap_or_default()),
+        ("VMC_SCORE", if States::in_match(status.state) { status.score.describe() } else { String::new() }),
+        ("VMC_MAP", status.map.clone().unwrap_or_default()),
+        ("VMC_MODE", status.mode.map(|mode| mode.name().to_owned()).unwrap_or_default()),
+        ("VMC_PROFILE", status.profile.clone().unwrap_or_default()),
+        ("VMC_TIMESTAMP", chrono::Local::now().to_rfc3339()),
+    ]
+}
+
+// Ejecuta el comando en la shell del sistema desde un hilo propio. La salida se envía al log línea a línea.
+pub fn run(command: String, env: Vec<(&'static str, String)>, config: &HookConfig) {
+    if !acquire(&RUNNING, config.max_concurrent) {
+        log::warn!("Hook skipped, there are already {} commands running: {}", config.max_concurrent, command);
+        return;
+    }
+
+    let timeout = Duration::from_secs(config.timeout_secs as u64);
+    thread::spawn(move || {
+        log::info!("Running hook: {}", command);
+        match execute(&command, env, timeout, Arc::new(|level, line| log::log!(level, "  | {}", line))) {
+            Ok(HookExit::Finished(status)) if status.success() => log::info!("Hook finished: {}", command),
+            Ok(HookExit::Finished(status)) => log::warn!("Hook failed ({}): {}", status, command),
+            Ok(HookExit::TimedOut) => log::warn!("Hook killed after {} s: {}", timeout.as_secs(), command),
+            Err(err) => log::error!("Couldn't run hook \"{}\": {}", command, err),
+        }
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+    });
+}
+
+// Reserva un hueco para un comando; false si ya hay max comandos en ejecución. Se libera restando 1 al terminar.
+fn acquire(running: &AtomicUsize, max: usize) -> bool {
+    if running.fetch_add(1, Ordering::SeqCst) >= max {
+        running.fetch_sub(1, Ordering::SeqCst);
+        return false;
+    }
+    true
+}
+
+enum HookExit {
+    Finished(ExitStatus),
+    TimedOut, // Se ha terminado el proceso porque tardaba demasiado
+}
+
+// Cómo se trata cada línea de la salida del comando, con el nivel de log que le corresponde (stdout info, stderr warn)
+type OutputHandler = Arc<dyn Fn(log::Level, String) + Send + Sync>;
+
+// Ejecuta el comando y espera a que termine, o lo termina si tarda más que timeout
+fn execute(command: &str, env: Vec<(&'static str, String)>, timeout: Duration, output: OutputHandler) -> Result<HookExit, String> {
+    let mut child = shell(command).envs(env).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().map_err(|err| err.to_string())?;
+    let stdout = child.stdout.take().map(|stdout| read_output(stdout, log::Level::Info, output.clone()));
+    let stderr = child.stderr.take().map(|stderr| read_output(stderr, log::Level::Warn, output));
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                // Los procesos hijos del comando pueden mantener abierta la salida, así que no se espera a leerla
+                return Ok(HookExit::TimedOut);
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(err) => return Err(format!("couldn't wait for it: {}", err)),
+        }
+    };
+    stdout.map(|reader| reader.join());
+    stderr.map(|reader| reader.join());
+    Ok(HookExit::Finished(status))
+}
+
+fn read_output(output: impl Read + Send + 'static, level: log::Level, handler: OutputHandler) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            handler(level, line);
+        }
+    })
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000; // Para que no se abra una consola con cada comando
+
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").raw_arg(command).creation_flags(CREATE_NO_WINDOW);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Ejecuta el comando guardando su salida
+    fn execute_captured(command: &str, env: Vec<(&'static str, String)>, timeout: Duration) -> (Result<HookExit, String>, Vec<(log::Level, String)>) {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let captured = lines.clone();
+        let result = execute(command, env, timeout, Arc::new(move |level, line| captured.lock().unwrap().push((level, line))));
+        let lines = lines.lock().unwrap().clone();
+        (result, lines)
+    }
+
+    #[test]
+    fn environment_and_output() {
+        let status = ControllerStatus { state: States::IN_GAME_PREPARING, map: Some("Ascent".to_owned()), profile: Some("Ranked".to_owned()), ..Default::default() };
+        let env = environment(HookTrigger::MatchStarted.key(), &status, States::LOADING, States::IN_GAME_PREPARING, Some(1));
+        let command = "echo \"$VMC_EVENT $VMC_PREV_STATE $VMC_STATE $VMC_ROUND $VMC_MAP $VMC_PROFILE\"; echo \"$VMC_STATE_NAME\" >&2; exit 3";
+
+        let (result, lines) = execute_captured(command, env, Duration::from_secs(10));
+        let Ok(HookExit::Finished(exit)) = result else { panic!("The command didn't finish"); };
+        assert_eq!(exit.code(), Some(3));
+        // La salida estándar va al log como información y la de error como aviso
+        assert!(lines.contains(&(log::Level::Info, "match_started loading buy_phase 1 Ascent Ranked".to_owned())), "{:?}", lines);
+        assert!(lines.contains(&(log::Level::Warn, "Buy phase".to_owned())), "{:?}", lines);
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn timeout_kills_the_command() {
+        let start = Instant::now();
+        let (result, lines) = execute_captured("sleep 5; echo late", Vec::new(), Duration::from_millis(200));
+        assert!(matches!(result, Ok(HookExit::TimedOut)));
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn concurrency_limit() {
+        let running = AtomicUsize::new(0);
+        assert!(acquire(&running, 2));
+        assert!(acquire(&running, 2));
+        assert!(!acquire(&running, 2));
+        assert_eq!(running.load(Ordering::SeqCst), 2);
+
+        // Al terminar un comando se puede ejecutar otro
+        running.fetch_sub(1, Ordering::SeqCst);
+        assert!(acquire(&running, 2));
+    }
+
+    #[test]
+    fn hook_filters() {
+        let hook = Hook { trigger: HookTrigger::StateChanged, to: Some(States::IN_GAME_PLAYING), command: "true".to_owned(), ..Default::default() };
+        assert!(hook.matches(HookTrigger::StateChanged, States::IN_GAME_PREPARING, States::IN_GAME_PLAYING));
+        assert!(!hook.matches(HookTrigger::StateChanged, States::IN_GAME_PLAYING, States::IN_GAME_PREPARING));
+        assert!(!hook.matches(HookTrigger::MatchStarted, States::IN_GAME_PREPARING, States::IN_GAME_PLAYING));
+        assert!(!Hook { enabled: false, ..hook.clone() }.matches(HookTrigger::StateChanged, States::IN_GAME_PREPARING, States::IN_GAME_PLAYING));
+        assert!(!Hook { command: " ".to_owned(), ..hook }.matches(HookTrigger::StateChanged, States::IN_GAME_PREPARING, States::IN_GAME_PLAYING));
+    }
+}
@@ -0,0 +1,162 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+
+use super::commands::{self, ControllerCommand};
+
+// Puntos porcentuales que suben o bajan los atajos de volumen
+const NUDGE_STEP: i8 = 5;
+
+// Atajos de teclado globales, en el formato "Ctrl+Alt+S"; vacío para no usar el atajo
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    pub enabled: bool,
+    pub suspend: String,
+    pub cycle_profile: String,
+    pub restore: String,
+    pub volume_up: String,
+    pub volume_down: String,
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            suspend: "Ctrl+Alt+S".to_owned(),
+            cycle_profile: "Ctrl+Alt+P".to_owned(),
+            restore: "Ctrl+Alt+R".to_owned(),
+            volume_up: "Ctrl+Alt+Up".to_owned(),
+            volume_down: "Ctrl+Alt+Down".to_owned(),
+        }
+    }
+}
+
+impl HotkeyConfig {
+    // (descripción, atajo, orden) de cada atajo, en el orden en el que se muestran
+    pub fn bindings(&mut self) -> [(&'static str, &mut String, ControllerCommand); 5] {
+        [
+            ("Suspend/resume automatic control", &mut self.suspend, ControllerCommand::ToggleSuspend),
+            ("Next profile", &mut self.cycle_profile, ControllerCommand::CycleProfile),
+            ("Restore original volume", &mut self.restore, ControllerCommand::RestoreOriginal),
+            ("Volume up (current state)", &mut self.volume_up, ControllerCommand::NudgeVolume(NUDGE_STEP)),
+            ("Volume down (current state)", &mut self.volume_down, ControllerCommand::NudgeVolume(-NUDGE_STEP)),
+        ]
+    }
+}
+
+// Orden asociada al identificador de cada atajo registrado. El manejador de eventos de la librería solo se puede
+// establecer una vez, así que consulta esta lista en lugar de capturar los atajos.
+static ACTIONS: Mutex<Vec<(u32, ControllerCommand)>> = Mutex::new(Vec::new());
+
+// Registro de los atajos en el sistema. En Windows se usa RegisterHotKey y en Linux, X11 (XGrabKey), por lo que
+// también funciona en un servidor X virtual (Xvfb).
+#[derive(Default)]
+pub struct Hotkeys {
+    manager: Option<GlobalHotKeyManager>,
+    registered: Vec<HotKey>,
+}
+
+impl Hotkeys {
+    // Registra los atajos de la configuración, sustituyendo a los anteriores. Retorna los errores de cada atajo.
+    pub fn apply(&mut self, config: &mut HotkeyConfig) -> Vec<String> {
+        if let Some(manager) = &self.manager {
+            let _ = manager.unregister_all(&self.registered);
+        }
+        self.registered.clear();
+        ACTIONS.lock().unwrap().clear();
+        if !config.enabled { return Vec::new(); }
+
+        if self.manager.is_none() {
+            match GlobalHotKeyManager::new() {
+                Ok(manager) => {
+                    GlobalHotKeyEvent::set_event_handler(Some(handle_event));
+                    self.manager = Some(manager);
+                }
+                Err(err) => return vec![format!("Global hotkeys are not available: {}", err)],
+            }
+        }
+        let manager = self.manager.as_ref().unwrap();
+
+        let mut errors = Vec::new();
+        for (description, key, command) in config.bindings() {
+            if key.trim().is_empty() { continue; }
+
+            let result = HotKey::from_str(key.trim())
+                .map_err(|err| err.to_string())
+                .and_then(|hotkey| manager.register(hotkey).map(|_| hotkey).map_err(|err| err.to_string()));
+            match result {
+                Ok(hotkey) => {
+                    self.registered.push(hotkey);
+                    ACTIONS.lock().unwrap().push((hotkey.id(), command));
+                }
+                Err(err) => errors.push(format!("{} ({}): {}", description, key, err)),
+            }
+        }
+
+        for error in errors.iter() {
+            log::warn!("Couldn't register hotkey: {}", error);
+        }
+        log::info!("Registered {} global hotkeys", self.registered.len());
+        errors
+    }
+}
+
+fn handle_event(event: GlobalHotKeyEvent) {
+    if event.state() != HotKeyState::Pressed { return; }
+
+    let command = ACTIONS.lock().unwrap().iter().find(|(id, _)| *id == event.id()).map(|(_, command)| *command);
+    if let Some(command) = command {
+        if !commands::send(command) {
+            log::debug!("Hotkey ignored, the program is not active: {:?}", command);
+        }
+    }
+}
+
+// Necesita un servidor X y libxdo (la que usa enigo para simular las teclas), así que no se ejecuta por defecto. Usa los
+// atajos globales y el canal de órdenes del controlador, por lo que se tiene que ejecutar sola:
+//   xvfb-run -a cargo test hotkey_sends_its_command -- --ignored
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+
+    use super::*;
+
+    #[test]
+    #[ignore = "needs an X server, run it alone with xvfb-run"]
+    fn hotkey_sends_its_command() {
+        assert!(std::env::var_os("DISPLAY").is_some(), "DISPLAY is not set, run the test with xvfb-run or in an X session");
+        let commands = commands::connect();
+
+        let mut config = HotkeyConfig { enabled: true, ..Default::default() };
+        let mut hotkeys = Hotkeys::default();
+        assert!(hotkeys.apply(&mut config).is_empty());
+        assert_eq!(ACTIONS.lock().unwrap().len(), config.bindings().len());
+        // La librería registra los atajos en su propio hilo de X11
+        sleep(Duration::from_millis(500));
+
+        // Ctrl+Alt+S (suspend) y Ctrl+Alt+Down (volume_down)
+        let mut enigo = Enigo::new(&Settings::default()).expect("Couldn't connect to the X server");
+        for key in [Key::Unicode('s'), Key::DownArrow] {
+            enigo.key(Key::Control, Direction::Press).unwrap();
+            enigo.key(Key::Alt, Direction::Press).unwrap();
+            enigo.key(key, Direction::Click).unwrap();
+            enigo.key(Key::Alt, Direction::Release).unwrap();
+            enigo.key(Key::Control, Direction::Release).unwrap();
+        }
+
+        assert_eq!(commands.recv_timeout(Duration::from_secs(2)), Ok(ControllerCommand::ToggleSuspend));
+        assert_eq!(commands.recv_timeout(Duration::from_secs(2)), Ok(ControllerCommand::NudgeVolume(-NUDGE_STEP)));
+
+        // Sin atajos registrados no llega nada
+        config.enabled = false;
+        hotkeys.apply(&mut config);
+        assert!(ACTIONS.lock().unwrap().is_empty());
+        commands::disconnect();
+    }
+}
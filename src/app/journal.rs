@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+const JOURNAL_FILE: &str = "volume_journal.json";
+
+// Registro en disco del volumen original del proceso controlado. Se escribe antes del primer cambio de volumen y se
+// borra al restaurarlo, por lo que si existe al abrir la aplicación es que el programa no terminó correctamente.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+pub struct Journal {
+    pub process_name: String,
+    pub original_volume: f32,
+    pub paused: bool, // Si el programa pausó la reproducción (volumen 0) y no llegó a reanudarla
+}
+
+impl Journal {
+    pub fn new(process_name: String, original_volume: f32) -> Self {
+        Self { process_name, original_volume, paused: false }
+    }
+
+    pub fn load() -> Option<Self> {
+        Self::load_from(&path()?)
+    }
+
+    fn load_from(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(journal) => Some(journal),
+            Err(err) => {
+                log::warn!("Ignoring invalid volume journal: {}", err);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = path() else { return; };
+        if let Err(err) = self.save_to(&path) {
+            log::error!("Couldn't write the volume journal: {}", err);
+        }
+    }
+
+    fn save_to(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+
+        // Se escribe en un archivo temporal y se renombra para no dejar un registro a medias si el programa se cierra durante la escritura
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(self).unwrap())
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+    }
+
+    pub fn clear() {
+        if let Some(path) = path() {
+            Self::clear_at(&path);
+        }
+    }
+
+    fn clear_at(path: &Path) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    super::data_path(JOURNAL_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vmc-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = temp_path("journal.json");
+        let mut journal = Journal::new("Spotify.exe".to_owned(), 0.8);
+        journal.save_to(&path).unwrap();
+
+        // Cada escritura sustituye a la anterior entera y no deja el archivo temporal
+        journal.paused = true;
+        journal.original_volume = 0.6;
+        journal.save_to(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+
+        let loaded = Journal::load_from(&path).unwrap();
+        assert_eq!((loaded.process_name.as_str(), loaded.original_volume, loaded.paused), ("Spotify.exe", 0.6, true));
+
+        Journal::clear_at(&path);
+        assert!(!path.exists());
+        assert!(Journal::load_from(&path).is_none());
+    }
+
+    #[test]
+    fn invalid_journal_is_ignored() {
+        let path = temp_path("invalid-journal.json");
+        // Un registro cortado a mitad de la escritura (ej. de una versión que no usaba el archivo temporal)
+        std::fs::write(&path, "{\"process_name\":\"Spotify.exe\",\"original_vol").unwrap();
+        assert!(Journal::load_from(&path).is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+}
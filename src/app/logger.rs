@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Número máximo de mensajes que se guardan en memoria para el visor de logs
+const MAX_ENTRIES: usize = 2000;
+const LOG_FILE: &str = "controller.log";
+// Cuando el archivo de log supera este tamaño se rota, guardando como máximo MAX_ROTATED_FILES archivos antiguos
+const MAX_LOG_FILE_SIZE: u64 = 1024 * 1024;
+const MAX_ROTATED_FILES: u32 = 3;
+
+#[derive(Clone)]
+pub struct LogEntry {
+    pub time: String,
+    pub level: log::Level,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {:<5} {}", self.time, self.level, self.message)
+    }
+}
+
+static ENTRIES: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+static LOG_FILE_HANDLE: Mutex<Option<File>> = Mutex::new(None);
+static LOG_FILE_PATH: Mutex<Option<PathBuf>> = Mutex::new(None); // None para usar la ruta por defecto
+
+// Envía los mensajes a stderr con env_logger (según RUST_LOG) y, si son de la aplicación, al visor de logs y al archivo de log
+struct AppLogger {
+    stderr: env_logger::Logger,
+}
+
+impl log::Log for AppLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        is_app_target(metadata.target()) || self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+        }
+        if !is_app_target(record.target()) || record.level() > log::Level::Debug { return; }
+
+        let entry = LogEntry {
+            time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            level: record.level(),
+            message: record.args().to_string(),
+        };
+        write_to_file(&entry);
+
+        let mut entries = ENTRIES.lock().unwrap();
+        if entries.len() == MAX_ENTRIES { entries.pop_front(); }
+        entries.push_back(entry);
+    }
+
+    fn flush(&self) {
+        if let Some(file) = LOG_FILE_HANDLE.lock().unwrap().as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+pub fn init() {
+    let stderr = env_logger::Builder::from_default_env().build();
+    log::set_max_level(stderr.filter().max(log::LevelFilter::Debug));
+    let _ = log::set_boxed_logger(Box::new(AppLogger { stderr }));
+}
+
+pub fn entries() -> Vec<LogEntry> {
+    ENTRIES.lock().unwrap().iter().cloned().collect()
+}
+
+pub fn clear() {
+    ENTRIES.lock().unwrap().clear();
+}
+
+pub fn file_path() -> Option<PathBuf> {
+    LOG_FILE_PATH.lock().unwrap().clone().or_else(default_file_path)
+}
+
+pub fn default_file_path() -> Option<PathBuf> {
+    super::data_path(LOG_FILE)
+}
+
+// Cambia la ruta del archivo de log; si se está escribiendo en el archivo, se continúa en el nuevo
+pub fn set_file_path(path: Option<PathBuf>) {
+    *LOG_FILE_PATH.lock().unwrap() = path;
+    let mut handle = LOG_FILE_HANDLE.lock().unwrap();
+    if handle.is_some() {
+        *handle = open_log_file();
+    }
+}
+
+// Activa o desactiva la escritura de los mensajes en el archivo de log
+pub fn set_file_logging(enabled: bool) {
+    let mut handle = LOG_FILE_HANDLE.lock().unwrap();
+    if !enabled {
+        *handle = None;
+    }
+    else if handle.is_none() {
+        *handle = open_log_file();
+    }
+}
+
+fn is_app_target(target: &str) -> bool {
+    target.starts_with(env!("CARGO_CRATE_NAME"))
+}
+
+fn open_log_file() -> Option<File> {
+    let path = file_path()?;
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+fn write_to_file(entry: &LogEntry) {
+    let mut handle = LOG_FILE_HANDLE.lock().unwrap();
+    let Some(file) = handle.as_mut() else { return; };
+    let _ = writeln!(file, "{}", entry);
+
+    if file.metadata().map_or(false, |metadata| metadata.len() > MAX_LOG_FILE_SIZE) {
+        *handle = None; // Se cierra el archivo antes de renombrarlo
+        rotate_log_files();
+        *handle = open_log_file();
+    }
+}
+
+// controller.log -> controller.log.1 -> controller.log.2 -> ... (el más antiguo se elimina)
+fn rotate_log_files() {
+    let Some(path) = file_path() else { return; };
+    let rotated = |index: u32| PathBuf::from(format!("{}.{}", path.display(), index));
+
+    let _ = std::fs::remove_file(rotated(MAX_ROTATED_FILES));
+    for index in (1..MAX_ROTATED_FILES).rev() {
+        let _ = std::fs::rename(rotated(index), rotated(index + 1));
+    }
+    let _ = std::fs::rename(&path, rotated(1));
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use super::constants::*;
+use super::outcomes::Team;
+
+const DEFAULT_MATCHERS: &str = include_str!("../../assets/matchers.toml");
+const USER_MATCHERS_FILE: &str = "matchers.toml";
+
+// Formato de cada línea del log: "[fecha][código]Categoría: texto"
+const LINE_FORMAT: &str = r"^\[(?P<date>[^\]]+)\]\[(?P<code>[^\]]+)\](?P<name>[^\:]+):\s*(?P<text>.+)$";
+
+// Eventos de la partida que se detectan en el log del juego
+#[derive(Debug, PartialEq)]
+pub enum GameEvent {
+    MatchStarted,
+    RoundEnded(u32, Option<Team>), // Número de la ronda que ha terminado, empezando en 0, y equipo ganador si se conoce
+    RoundStarted,
+    MatchEnded(Option<String>), // Equipo ganador
+    QueueDetected(GameMode),
+    GameModeLoaded(Option<GameMode>), // None si el modo cargado no indica el modo de la partida (ej. competitivo y no competitivo)
+    MapLoaded(String), // Nombre del mapa en el juego
+    Pregame(u8), // Estado antes de la partida (menús, cola, selección de agente...)
+    PlayerTeam(Team),
+    MatchAborted, // La partida termina sin resultado (ej. se cancela)
+}
+
+// Tipo de evento que genera una regla; los valores del evento salen de los grupos con nombre de la expresión regular
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchEvent {
+    MatchStarted,
+    MatchEnded,
+    RoundStarted,
+    RoundEnded,
+    Queue,
+    GameMode,
+    Map,
+    Menus,
+    InQueue,
+    MatchFound,
+    AgentSelect,
+    Loading,
+    PlayerTeam,
+    MatchAborted,
+}
+
+impl MatchEvent {
+    pub const ALL: [MatchEvent; 14] = [
+        MatchEvent::MatchStarted, MatchEvent::MatchEnded, MatchEvent::RoundStarted, MatchEvent::RoundEnded, MatchEvent::Queue, MatchEvent::GameMode, MatchEvent::Map,
+        MatchEvent::Menus, MatchEvent::InQueue, MatchEvent::MatchFound, MatchEvent::AgentSelect, MatchEvent::Loading,
+        MatchEvent::PlayerTeam, MatchEvent::MatchAborted,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatchEvent::MatchStarted => "Match started",
+            MatchEvent::MatchEnded => "Match ended",
+            MatchEvent::RoundStarted => "Round started",
+            MatchEvent::RoundEnded => "Round ended",
+            MatchEvent::Queue => "Queue",
+            MatchEvent::GameMode => "Game mode",
+            MatchEvent::Map => "Map",
+            MatchEvent::Menus => "Menus",
+            MatchEvent::InQueue => "In queue",
+            MatchEvent::MatchFound => "Match found",
+            MatchEvent::AgentSelect => "Agent select",
+            MatchEvent::Loading => "Loading",
+            MatchEvent::PlayerTeam => "Player team",
+            MatchEvent::MatchAborted => "Match aborted",
+        }
+    }
+
+    // Convierte los grupos capturados en el evento; None si los valores no son válidos (ej. un mapa desconocido)
+    fn game_event(&self, values: &HashMap<&str, &str>) -> Option<GameEvent> {
+        match self {
+            MatchEvent::MatchStarted => Some(GameEvent::MatchStarted),
+            MatchEvent::MatchEnded => Some(GameEvent::MatchEnded(values.get("team").filter(|team| !team.is_empty()).map(|team| team.to_string()))),
+            MatchEvent::RoundStarted => Some(GameEvent::RoundStarted),
+            MatchEvent::RoundEnded => Some(GameEvent::RoundEnded(
+                values.get("round").and_then(|round| round.parse().ok()).unwrap_or(0),
+                values.get("team").and_then(|team| Team::from_log(team))
+            )),
+            MatchEvent::Queue => GameMode::from_queue(values.get("queue")?).map(GameEvent::QueueDetected),
+            MatchEvent::GameMode => Some(GameEvent::GameModeLoaded(GameMode::from_game_mode_path(values.get("mode")?))),
+            MatchEvent::Map => map_name(values.get("map")?).map(|map| GameEvent::MapLoaded(map.to_owned())),
+            MatchEvent::Menus => Some(GameEvent::Pregame(States::MENUS)),
+            MatchEvent::InQueue => Some(GameEvent::Pregame(States::IN_QUEUE)),
+            MatchEvent::MatchFound => Some(GameEvent::Pregame(States::MATCH_FOUND)),
+            MatchEvent::AgentSelect => Some(GameEvent::Pregame(States::AGENT_SELECT)),
+            MatchEvent::Loading => Some(GameEvent::Pregame(States::LOADING)),
+            MatchEvent::PlayerTeam => Team::from_log(values.get("team")?).map(GameEvent::PlayerTeam),
+            MatchEvent::MatchAborted => Some(GameEvent::MatchAborted),
+        }
+    }
+}
+
+// Regla que asocia las líneas del log con un evento. Se tienen que cumplir todas las condiciones indicadas.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MatcherRule {
+    pub name: String, // Una regla del usuario con el mismo nombre que una por defecto la sustituye
+    pub event: MatchEvent,
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excludes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+}
+
+fn default_enabled() -> bool { true }
+fn is_enabled(enabled: &bool) -> bool { *enabled }
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct MatcherFile {
+    #[serde(default)]
+    matchers: Vec<MatcherRule>,
+}
+
+pub fn defaults() -> Vec<MatcherRule> {
+    toml::from_str::<MatcherFile>(DEFAULT_MATCHERS).expect("Invalid default matchers").matchers
+}
+
+fn user_path() -> Option<PathBuf> {
+    super::data_path(USER_MATCHERS_FILE)
+}
+
+// Reglas del usuario; si no hay archivo no hay ninguna
+pub fn load_user() -> Result<Vec<MatcherRule>, String> {
+    let Some(path) = user_path().filter(|path| path.exists()) else { return Ok(Vec::new()); };
+    let contents = std::fs::read_to_string(&path).map_err(|err| format!("Couldn't read {}: {}", path.display(), err))?;
+    toml::from_str::<MatcherFile>(&contents).map(|file| file.matchers).map_err(|err| format!("Invalid log matchers file {}:\n{}", path.display(), err))
+}
+
+pub fn save_user(rules: &[MatcherRule]) -> Result<(), String> {
+    Matchers::new(rules.to_vec())?; // Solo se guardan reglas válidas
+    let path = user_path().ok_or("There is no folder to save the log matchers")?;
+    let contents = toml::to_string_pretty(&MatcherFile { matchers: rules.to_vec() }).map_err(|err| format!("Couldn't write the log matchers: {}", err))?;
+    std::fs::write(&path, contents).map_err(|err| format!("Couldn't write {}: {}", path.display(), err))
+}
+
+// Reglas en el orden en el que se comprueban: las del usuario que sustituyen a una por defecto ocupan su lugar y las
+// nuevas van antes que todas las por defecto
+pub fn merge(defaults: Vec<MatcherRule>, user: &[MatcherRule]) -> Vec<MatcherRule> {
+    let mut rules: Vec<MatcherRule> = user.iter().filter(|rule| !defaults.iter().any(|default| default.name == rule.name)).cloned().collect();
+    rules.extend(defaults.into_iter().map(|default| user.iter().find(|rule| rule.name == default.name).cloned().unwrap_or(default)));
+    rules
+}
+
+// Hora (UTC) a la que se escribió una línea del log | [2024.08.31-18.32.40:120][12]... |
+pub fn log_time(line: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let date = line.strip_prefix('[')?.split(']').next()?;
+    chrono::NaiveDateTime::parse_from_str(date, "%Y.%m.%d-%H.%M.%S:%3f").ok().map(|time| time.and_utc())
+}
+
+// Reglas compiladas con las que se analiza cada línea del log
+pub struct Matchers {
+    line: Regex,
+    rules: Vec<(MatcherRule, Option<Regex>)>,
+}
+
+impl Matchers {
+    pub fn new(rules: Vec<MatcherRule>) -> Result<Self, String> {
+        let mut compiled = Vec::new();
+        for rule in rules.into_iter().filter(|rule| rule.enabled) {
+            let regex = match &rule.regex {
+                Some(regex) => Some(Regex::new(regex).map_err(|err| format!("Rule \"{}\": invalid regex: {}", rule.name, err))?),
+                None => None,
+            };
+            compiled.push((rule, regex));
+        }
+        Ok(Self { line: Regex::new(LINE_FORMAT).unwrap(), rules: compiled })
+    }
+
+    // Reglas por defecto con las del usuario. Si las del usuario no son válidas se usan solo las por defecto.
+    pub fn load() -> Self {
+        let user = load_user().unwrap_or_else(|err| {
+            log::error!("{}", err);
+            Vec::new()
+        });
+        Self::new(merge(defaults(), &user)).unwrap_or_else(|err| {
+            log::error!("Ignoring the custom log matchers: {}", err);
+            Self::new(defaults()).unwrap()
+        })
+    }
+
+    // Retorna el nombre de la primera regla que coincide con la línea y el evento que genera
+    pub fn analyze(&self, line: &str) -> Option<(&str, GameEvent)> {
+        let captures = self.line.captures(line)?;
+        let (category, text) = (&captures["name"], &captures["text"]);
+
+        self.rules.iter().find_map(|(rule, regex)| {
+            if rule.category.as_deref().is_some_and(|expected| expected != category) { return None; }
+            if rule.contains.as_deref().is_some_and(|contains| !text.contains(contains)) { return None; }
+            if rule.excludes.as_deref().is_some_and(|excludes| text.contains(excludes)) { return None; }
+
+            let mut values = HashMap::new();
+            if let Some(regex) = regex {
+                let captures = regex.captures(text)?;
+                for name in regex.capture_names().flatten() {
+                    if let Some(value) = captures.name(name) { values.insert(name, value.as_str()); }
+                }
+            }
+            rule.event.game_event(&values).map(|event| (rule.name.as_str(), event))
+        })
+    }
+
+    // Si alguna regla da el valor de un grupo del evento. Las reglas por defecto no dan el ganador de cada ronda ni el
+    // equipo del jugador, así que sin reglas del usuario no se conocen el marcador ni los resultados.
+    fn provides(&self, event: MatchEvent, group: &str) -> bool {
+        self.rules.iter().any(|(rule, regex)| rule.event == event && regex.as_ref().is_some_and(|regex| regex.capture_names().flatten().any(|name| name == group)))
+    }
+
+    // Ganador de cada ronda, para el marcador y el punto de partido
+    pub fn detects_round_winner(&self) -> bool {
+        self.provides(MatchEvent::RoundEnded, "team")
+    }
+
+    // Equipo del jugador, para saber si ha ganado o perdido
+    pub fn detects_player_team(&self) -> bool {
+        self.provides(MatchEvent::PlayerTeam, "team")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::outcomes::{Outcome, Score};
+    use super::*;
+
+    // Líneas reales del log
+    const MATCH_STARTED: &str = "[2024.08.31-18.33.07:287][277]LogGameFlowStateManager: Reconcile called with state: TransitionToInGame and new state: InGame. Changing state.";
+    const ROUND_ENDED: &str = "[2024.08.31-17.25.31:152][599]LogShooterGameState: Warning: AShooterGameState::OnRoundEnded for round '22'";
+    const ROUND_STARTED: &str = "[2024.08.31-18.36.09:234][254]LogShooterGameState: Warning: Gameplay started at local time 30.218750 (server time 30.292187)";
+    const RESPAWN: &str = "[2024.08.31-18.39.35:655][913]LogShooterGameState: Warning: Gameplay started at local time 0.000000 (server time 0.000000)";
+    const MATCH_ENDED: &str = "[2024.08.31-17.27.38:105][866]LogShooterGameState: Match Ended: Completion State: ''. Winning Team: 'Blue' ()";
+    const CLOTHING: &str = "[2024.08.31-19.11.00:337][867]LogSkeletalMesh: Warning: USkeletalMeshComponent::RecreateClothingActors : (CosmeticCharacterMesh3P) Smonk_PC_C_2147249944";
+    const MINIMAP: &str = "[2024.08.31-18.51.39:595][870]LogAresMinimapComponent: Warning: Found Compute Position override on: MinimapRangeIndicator. Setting Position source to custom. Please change this in the asset.";
+
+    fn analyze(line: &str) -> Option<(String, GameEvent)> {
+        let matchers = Matchers::new(defaults()).unwrap();
+        matchers.analyze(line).map(|(name, event)| (name.to_owned(), event))
+    }
+
+    // Cambio de estado del juego con el mismo mensaje que la línea real del inicio de la partida
+    fn reconcile(prev: &str, next: &str) -> String {
+        MATCH_STARTED.replace("state: TransitionToInGame and new state: InGame", &format!("state: {} and new state: {}", prev, next))
+    }
+
+    #[test]
+    fn match_lines() {
+        assert_eq!(analyze(MATCH_STARTED), Some(("Match started".to_owned(), GameEvent::MatchStarted)));
+        assert_eq!(analyze(ROUND_ENDED), Some(("Round ended".to_owned(), GameEvent::RoundEnded(22, None))));
+        assert_eq!(analyze(ROUND_STARTED), Some(("Round started".to_owned(), GameEvent::RoundStarted)));
+        assert_eq!(analyze(MATCH_ENDED), Some(("Match ended".to_owned(), GameEvent::MatchEnded(Some("Blue".to_owned())))));
+        assert_eq!(analyze(RESPAWN), None);
+        assert_eq!(analyze(CLOTHING), None);
+        assert_eq!(analyze(MINIMAP), None);
+    }
+
+    #[test]
+    fn match_result() {
+        let Some((_, GameEvent::MatchEnded(Some(winner)))) = analyze(MATCH_ENDED) else { panic!("The match end has no winner"); };
+        let winner = Team::from_log(&winner).unwrap();
+
+        // El equipo del jugador lo tiene que indicar una regla del usuario (player_team)
+        assert_eq!(Score::default().match_outcome(winner), None);
+        assert_eq!(Score { team: Some(Team::Blue), ..Default::default() }.match_outcome(winner), Some(Outcome::MatchWon));
+        assert_eq!(Score { team: Some(Team::Red), ..Default::default() }.match_outcome(winner), Some(Outcome::MatchLost));
+
+        // El final de ronda no indica el ganador, así que el marcador no cambia
+        let mut score = Score { team: Some(Team::Red), ..Default::default() };
+        let Some((_, GameEvent::RoundEnded(_, winner))) = analyze(ROUND_ENDED) else { panic!("Not a round end"); };
+        assert_eq!(winner.and_then(|winner| score.round_won(winner)), None);
+        assert_eq!(score, Score { team: Some(Team::Red), red: 0, blue: 0 });
+    }
+
+    #[test]
+    fn pregame_lines() {
+        assert_eq!(analyze(&reconcile("TransitionToMainMenu", "MainMenu")).map(|(_, event)| event), Some(GameEvent::Pregame(States::MENUS)));
+        assert_eq!(analyze(&reconcile("MainMenu", "Pregame")).map(|(_, event)| event), Some(GameEvent::Pregame(States::AGENT_SELECT)));
+        assert_eq!(analyze(&reconcile("Pregame", "TransitionToInGame")).map(|(_, event)| event), Some(GameEvent::Pregame(States::LOADING)));
+        // El inicio de la partida no es la carga aunque el estado anterior sea TransitionToInGame
+        assert_eq!(analyze(MATCH_STARTED).map(|(_, event)| event), Some(GameEvent::MatchStarted));
+    }
+
+    #[test]
+    fn aborted_match_goes_before_ended() {
+        let rules = defaults();
+        let position = |name: &str| rules.iter().position(|rule| rule.name == name).unwrap();
+        assert!(position("Match aborted") < position("Match ended"));
+
+        // La línea real tiene el estado vacío, así que es un final normal; con otro estado la partida no tiene resultado
+        let remake = MATCH_ENDED.replace("Completion State: ''", "Completion State: 'Remake'");
+        assert_eq!(analyze(&remake), Some(("Match aborted".to_owned(), GameEvent::MatchAborted)));
+    }
+
+    #[test]
+    fn no_unverified_default_rules() {
+        // Sin líneas reales del log no hay reglas por defecto para estos eventos
+        let unverified = [MatchEvent::Queue, MatchEvent::GameMode, MatchEvent::Map, MatchEvent::InQueue, MatchEvent::MatchFound, MatchEvent::PlayerTeam];
+        assert!(defaults().iter().all(|rule| !unverified.contains(&rule.event)));
+    }
+
+    #[test]
+    fn custom_queue_rule() {
+        // Las reglas del usuario que no sustituyen a una por defecto se comprueban antes que todas las por defecto
+        let rule = MatcherRule {
+            name: "Searching".to_owned(),
+            event: MatchEvent::InQueue,
+            enabled: true,
+            category: Some("LogMatchmaking".to_owned()),
+            contains: Some("Searching for a match".to_owned()),
+            excludes: None,
+            regex: None,
+        };
+        let matchers = Matchers::new(merge(defaults(), &[rule])).unwrap();
+        let line = "[2024.08.31-18.30.02:511][120]LogMatchmaking: Searching for a match"; // Inventada, solo tiene que cumplir la regla
+        assert_eq!(matchers.analyze(line), Some(("Searching", GameEvent::Pregame(States::IN_QUEUE))));
+        assert_eq!(analyze(line), None);
+    }
+
+    #[test]
+    fn detected_results() {
+        // Con las reglas por defecto no se conoce el marcador ni el resultado para el jugador
+        let matchers = Matchers::new(defaults()).unwrap();
+        assert!(!matchers.detects_round_winner());
+        assert!(!matchers.detects_player_team());
+
+        let rule = |name: &str, event, regex: &str, enabled| MatcherRule {
+            name: name.to_owned(), event, enabled, category: None, contains: None, excludes: None, regex: Some(regex.to_owned()),
+        };
+        let user = [
+            rule("Round ended", MatchEvent::RoundEnded, "OnRoundEnded for round '(?P<round>\\d+)', winner '(?P<team>\\w+)'", true),
+            rule("Team", MatchEvent::PlayerTeam, "Local player team: (?P<team>\\w+)", false),
+        ];
+        let matchers = Matchers::new(merge(defaults(), &user)).unwrap();
+        assert!(matchers.detects_round_winner());
+        // Las reglas desactivadas no cuentan
+        assert!(!matchers.detects_player_team());
+
+        // Una regla sin el grupo del equipo tampoco da el valor
+        let user = [rule("Team", MatchEvent::PlayerTeam, "Local player team", true)];
+        assert!(!Matchers::new(merge(defaults(), &user)).unwrap().detects_player_team());
+    }
+}
@@ -0,0 +1,264 @@
+use super::constants::*;
+
+// Equipo de la partida tal y como aparece en el log ("Red" o "Blue")
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Team {
+    Red,
+    Blue,
+}
+
+impl Team {
+    // None si no es un equipo (ej. en combate a muerte gana un jugador)
+    pub fn from_log(name: &str) -> Option<Team> {
+        match name.trim().to_lowercase().as_str() {
+            "red" => Some(Team::Red),
+            "blue" => Some(Team::Blue),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Team::Red => "Red",
+            Team::Blue => "Blue",
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Side {
+    Attack,
+    Defense,
+}
+
+impl Side {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Side::Attack => "Attack",
+            Side::Defense => "Defense",
+        }
+    }
+}
+
+// Resultado de una ronda o de la partida para el jugador; solo se conoce si el log indica su equipo
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    RoundWon,
+    RoundLost,
+    MatchWon,
+    MatchLost,
+}
+
+impl Outcome {
+    pub const ALL: [Outcome; 4] = [Outcome::RoundWon, Outcome::RoundLost, Outcome::MatchWon, Outcome::MatchLost];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::RoundWon => "Round won",
+            Outcome::RoundLost => "Round lost",
+            Outcome::MatchWon => "Match won",
+            Outcome::MatchLost => "Match lost",
+        }
+    }
+}
+
+// Marcador de la partida actual
+#[derive(serde::Serialize, Clone, Copy, Default, PartialEq, Debug)]
+pub struct Score {
+    pub team: Option<Team>, // Equipo del jugador
+    pub red: u32,
+    pub blue: u32,
+}
+
+impl Score {
+    pub fn rounds(&self, team: Team) -> u32 {
+        match team {
+            Team::Red => self.red,
+            Team::Blue => self.blue,
+        }
+    }
+
+    // Suma la ronda al equipo ganador y retorna el resultado para el jugador
+    pub fn round_won(&mut self, winner: Team) -> Option<Outcome> {
+        match winner {
+            Team::Red => self.red += 1,
+            Team::Blue => self.blue += 1,
+        }
+        self.team.map(|team| if team == winner { Outcome::RoundWon } else { Outcome::RoundLost })
+    }
+
+    pub fn match_outcome(&self, winner: Team) -> Option<Outcome> {
+        self.team.map(|team| if team == winner { Outcome::MatchWon } else { Outcome::MatchLost })
+    }
+
+    // Lado del jugador en una ronda (empezando en 1). El equipo rojo ataca en la primera mitad; en la prórroga se
+    // vuelve a los lados de la primera mitad y se cambia cada ronda.
+    pub fn side(&self, round: u32, mode: Option<GameMode>) -> Option<Side> {
+        let half = GameMode::rounds_per_half(mode)?;
+        let red_attacks = if round <= 2 * half { round <= half } else { (round - 2 * half) % 2 == 1 };
+        Some(if (self.team? == Team::Red) == red_attacks { Side::Attack } else { Side::Defense })
+    }
+
+    pub fn describe(&self) -> String {
+        match self.team {
+            Some(team) => {
+                let enemy = if team == Team::Red { Team::Blue } else { Team::Red };
+                format!("{} - {} ({})", self.rounds(team), self.rounds(enemy), team.name())
+            }
+            None => format!("Red {} - {} Blue", self.red, self.blue),
+        }
+    }
+}
+
+// Momentos especiales de la partida según la ronda y el marcador
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundContext {
+    HalfTime, // Primera ronda de la segunda mitad, con la pausa del cambio de lado
+    MatchPoint, // A un equipo le falta una ronda para ganar; requiere conocer el ganador de cada ronda
+    Overtime,
+}
+
+impl RoundContext {
+    pub const ALL: [RoundContext; 3] = [RoundContext::HalfTime, RoundContext::MatchPoint, RoundContext::Overtime];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoundContext::HalfTime => "Half-time",
+            RoundContext::MatchPoint => "Match point",
+            RoundContext::Overtime => "Overtime",
+        }
+    }
+
+    // Si se cumple en una ronda (empezando en 1). En la prórroga de competitivo hay que ganar por dos rondas, así que
+    // solo es punto de partido si un equipo va por delante; en el resto de modos se decide en una sola ronda.
+    pub fn applies(&self, round: u32, score: &Score, mode: Option<GameMode>) -> bool {
+        let Some(half) = GameMode::rounds_per_half(mode) else { return false; };
+        let overtime = round > 2 * half;
+        match self {
+            RoundContext::HalfTime => round == half + 1,
+            RoundContext::Overtime => overtime,
+            RoundContext::MatchPoint if score.red + score.blue + 1 != round => false, // No se conocen todos los ganadores
+            RoundContext::MatchPoint if overtime && matches!(mode, Some(GameMode::Competitive | GameMode::Custom)) => score.red != score.blue,
+            RoundContext::MatchPoint => score.red.max(score.blue) == half,
+        }
+    }
+}
+
+// Efecto de una acción sobre la música
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum OutcomeEffect {
+    Swell, // Cambia el volumen durante un tiempo y luego vuelve al del estado
+    NextTrack,
+}
+
+impl OutcomeEffect {
+    pub const ALL: [OutcomeEffect; 2] = [OutcomeEffect::Swell, OutcomeEffect::NextTrack];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutcomeEffect::Swell => "Swell volume",
+            OutcomeEffect::NextTrack => "Next track",
+        }
+    }
+}
+
+// Acción que aplica el controlador cuando se produce un resultado
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct OutcomeAction {
+    pub enabled: bool,
+    pub outcome: Outcome,
+    pub effect: OutcomeEffect,
+    pub level: u8, // Solo para Swell
+    pub seconds: u32,
+}
+
+impl Default for OutcomeAction {
+    fn default() -> Self {
+        Self { enabled: true, outcome: Outcome::RoundWon, effect: OutcomeEffect::Swell, level: 100, seconds: 5 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(team: Option<Team>, red: u32, blue: u32) -> Score {
+        Score { team, red, blue }
+    }
+
+    #[test]
+    fn round_and_match_outcome() {
+        let mut score = score(Some(Team::Blue), 0, 0);
+        assert_eq!(score.round_won(Team::Blue), Some(Outcome::RoundWon));
+        assert_eq!(score.round_won(Team::Red), Some(Outcome::RoundLost));
+        assert_eq!(score.round_won(Team::Blue), Some(Outcome::RoundWon));
+        assert_eq!((score.red, score.blue), (1, 2));
+        assert_eq!(score.match_outcome(Team::Blue), Some(Outcome::MatchWon));
+        assert_eq!(score.match_outcome(Team::Red), Some(Outcome::MatchLost));
+
+        // Sin el equipo del jugador se cuenta la ronda pero no hay resultado
+        let mut unknown = Score::default();
+        assert_eq!(unknown.round_won(Team::Red), None);
+        assert_eq!(unknown.red, 1);
+        assert_eq!(unknown.match_outcome(Team::Red), None);
+    }
+
+    #[test]
+    fn side() {
+        let red = score(Some(Team::Red), 0, 0);
+        let blue = score(Some(Team::Blue), 0, 0);
+        let competitive = Some(GameMode::Competitive);
+
+        assert_eq!(red.side(1, competitive), Some(Side::Attack));
+        assert_eq!(red.side(12, competitive), Some(Side::Attack));
+        assert_eq!(red.side(13, competitive), Some(Side::Defense));
+        assert_eq!(red.side(24, competitive), Some(Side::Defense));
+        assert_eq!(blue.side(1, competitive), Some(Side::Defense));
+        assert_eq!(blue.side(13, competitive), Some(Side::Attack));
+
+        // En la prórroga se vuelve a los lados de la primera mitad y se cambia cada ronda
+        assert_eq!(red.side(25, competitive), Some(Side::Attack));
+        assert_eq!(red.side(26, competitive), Some(Side::Defense));
+        assert_eq!(red.side(27, competitive), Some(Side::Attack));
+
+        let spike_rush = Some(GameMode::SpikeRush);
+        assert_eq!(red.side(3, spike_rush), Some(Side::Attack));
+        assert_eq!(red.side(4, spike_rush), Some(Side::Defense));
+
+        assert_eq!(red.side(1, Some(GameMode::Deathmatch)), None);
+        assert_eq!(score(None, 0, 0).side(1, competitive), None);
+    }
+
+    #[test]
+    fn round_context_in_regulation() {
+        let competitive = Some(GameMode::Competitive);
+        assert!(RoundContext::HalfTime.applies(13, &score(None, 7, 5), competitive));
+        assert!(!RoundContext::HalfTime.applies(12, &score(None, 6, 5), competitive));
+        assert!(RoundContext::HalfTime.applies(4, &score(None, 2, 1), Some(GameMode::SpikeRush)));
+        assert!(!RoundContext::Overtime.applies(24, &score(None, 12, 11), competitive));
+
+        assert!(RoundContext::MatchPoint.applies(23, &score(None, 12, 10), competitive));
+        assert!(!RoundContext::MatchPoint.applies(23, &score(None, 11, 11), competitive));
+        // Sin los ganadores de todas las rondas no se sabe si es punto de partido
+        assert!(!RoundContext::MatchPoint.applies(23, &score(None, 12, 9), competitive));
+
+        assert!(!RoundContext::HalfTime.applies(13, &score(None, 7, 5), Some(GameMode::Deathmatch)));
+    }
+
+    #[test]
+    fn round_context_in_overtime() {
+        let competitive = Some(GameMode::Competitive);
+        assert!(RoundContext::Overtime.applies(25, &score(None, 12, 12), competitive));
+        assert!(!RoundContext::HalfTime.applies(25, &score(None, 12, 12), competitive));
+
+        // En competitivo hay que ganar por dos rondas
+        assert!(!RoundContext::MatchPoint.applies(25, &score(None, 12, 12), competitive));
+        assert!(RoundContext::MatchPoint.applies(26, &score(None, 13, 12), competitive));
+        assert!(!RoundContext::MatchPoint.applies(27, &score(None, 13, 13), competitive));
+
+        // En el resto de modos la prórroga se decide en una ronda
+        assert!(RoundContext::MatchPoint.applies(25, &score(None, 12, 12), Some(GameMode::Unrated)));
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+// Ejecutable del cliente del juego (el del Riot Client no indica que el juego esté abierto)
+pub const GAME_PROCESS: &str = "VALORANT-Win64-Shipping.exe";
+
+// Cada cuánto se comprueba si el juego está abierto
+const CHECK_INTERVAL: Duration = Duration::from_secs(3);
+// Comprobaciones seguidas en las que tiene que cambiar el resultado para avisar, para que un fallo puntual al listar los
+// procesos no pare el programa en mitad de una partida
+const CONFIRM_CHECKS: u32 = 2;
+
+// Lista de procesos del sistema; cada sistema operativo la obtiene de una forma distinta
+pub trait ProcessList: Send {
+    // Si hay algún proceso con ese nombre de ejecutable, sin distinguir mayúsculas
+    fn is_running(&mut self, name: &str) -> bool;
+}
+
+// Lista de procesos del sistema actual; None si no se puede consultar en este sistema
+pub fn system() -> Option<Box<dyn ProcessList>> {
+    #[cfg(windows)]
+    return Some(Box::new(ToolhelpProcesses));
+    #[cfg(target_os = "linux")]
+    return Some(Box::new(ProcProcesses));
+    #[cfg(not(any(windows, target_os = "linux")))]
+    return None;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameProcessEvent {
+    Started,
+    Exited,
+}
+
+// Si el juego está abierto según las últimas comprobaciones
+#[derive(Default)]
+struct GameStatus {
+    running: bool,
+    changed_checks: u32, // Comprobaciones seguidas con el resultado contrario
+}
+
+impl GameStatus {
+    fn update(&mut self, running: bool) -> Option<GameProcessEvent> {
+        if running == self.running {
+            self.changed_checks = 0;
+            return None;
+        }
+        self.changed_checks += 1;
+        if self.changed_checks < CONFIRM_CHECKS { return None; }
+
+        self.running = running;
+        self.changed_checks = 0;
+        Some(if running { GameProcessEvent::Started } else { GameProcessEvent::Exited })
+    }
+}
+
+// Comprueba periódicamente si el juego está abierto desde un hilo propio y avisa cuando se abre o se cierra. Si el
+// juego ya está abierto al empezar, se avisa como si se acabara de abrir. El hilo se para al destruir el ProcessWatcher.
+pub struct ProcessWatcher {
+    stop: Arc<AtomicBool>,
+    events: mpsc::Receiver<GameProcessEvent>,
+}
+
+impl ProcessWatcher {
+    pub fn start(processes: Box<dyn ProcessList>, name: &'static str, ctx: egui::Context) -> Self {
+        Self::start_with_interval(processes, name, CHECK_INTERVAL, ctx)
+    }
+
+    fn start_with_interval(mut processes: Box<dyn ProcessList>, name: &'static str, interval: Duration, ctx: egui::Context) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut status = GameStatus::default();
+            while !thread_stop.load(Ordering::Relaxed) {
+                if let Some(event) = status.update(processes.is_running(name)) {
+                    log::info!("{} {}", name, if event == GameProcessEvent::Started { "started" } else { "exited" });
+                    if tx.send(event).is_err() { break; }
+                    ctx.request_repaint(); // Para atender el evento aunque la ventana esté oculta
+                }
+                thread::sleep(interval);
+            }
+        });
+        Self { stop, events: rx }
+    }
+
+    pub fn events(&self) -> Vec<GameProcessEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+impl Drop for ProcessWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(windows)]
+struct ToolhelpProcesses;
+
+#[cfg(windows)]
+impl ProcessList for ToolhelpProcesses {
+    fn is_running(&mut self, name: &str) -> bool {
+        use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+        use windows_sys::Win32::System::Diagnostics::ToolHelp::{CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS};
+
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == INVALID_HANDLE_VALUE { return false; }
+
+            let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+            let mut found = false;
+            let mut more = Process32FirstW(snapshot, &mut entry) != 0;
+            while more && !found {
+                let length = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+                found = String::from_utf16_lossy(&entry.szExeFile[..length]).eq_ignore_ascii_case(name);
+                more = Process32NextW(snapshot, &mut entry) != 0;
+            }
+            CloseHandle(snapshot);
+            found
+        }
+    }
+}
+
+// En Linux el juego se ejecuta con Wine o Proton, así que se busca el ejecutable en la línea de comandos de cada proceso
+#[cfg(target_os = "linux")]
+struct ProcProcesses;
+
+#[cfg(target_os = "linux")]
+impl ProcessList for ProcProcesses {
+    fn is_running(&mut self, name: &str) -> bool {
+        let Ok(entries) = std::fs::read_dir("/proc") else { return false; };
+        entries.flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().chars().all(|c| c.is_ascii_digit()))
+            .any(|entry| {
+                let Ok(cmdline) = std::fs::read(entry.path().join("cmdline")) else { return false; };
+                let program = String::from_utf8_lossy(cmdline.split(|&byte| byte == 0).next().unwrap_or_default()).into_owned();
+                program.rsplit(['/', '\\']).next().is_some_and(|file| file.eq_ignore_ascii_case(name))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Instant;
+
+    use super::*;
+
+    // Lista de procesos que responde lo indicado en cada comprobación y repite la última respuesta
+    struct FakeProcesses {
+        answers: VecDeque<bool>,
+        checks: Arc<AtomicUsize>,
+    }
+
+    impl ProcessList for FakeProcesses {
+        fn is_running(&mut self, name: &str) -> bool {
+            assert_eq!(name, GAME_PROCESS);
+            self.checks.fetch_add(1, Ordering::SeqCst);
+            if self.answers.len() > 1 { self.answers.pop_front().unwrap() } else { self.answers[0] }
+        }
+    }
+
+    fn watch(answers: &[bool]) -> (ProcessWatcher, Arc<AtomicUsize>) {
+        let checks = Arc::new(AtomicUsize::new(0));
+        let processes = FakeProcesses { answers: answers.iter().copied().collect(), checks: checks.clone() };
+        let watcher = ProcessWatcher::start_with_interval(Box::new(processes), GAME_PROCESS, Duration::from_millis(5), egui::Context::default());
+        (watcher, checks)
+    }
+
+    fn wait_for_checks(checks: &AtomicUsize, count: usize) {
+        let start = Instant::now();
+        while checks.load(Ordering::SeqCst) < count {
+            assert!(start.elapsed() < Duration::from_secs(5), "The watcher stopped checking");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn game_started_and_exited() {
+        // Un fallo puntual en una comprobación no cuenta como abrir o cerrar el juego
+        let answers = [false, true, false, true, true, true, false, true, true, false, false, false];
+        let (watcher, checks) = watch(&answers);
+        wait_for_checks(&checks, answers.len() + 2);
+        assert_eq!(watcher.events(), [GameProcessEvent::Started, GameProcessEvent::Exited]);
+    }
+
+    #[test]
+    fn game_already_running() {
+        let (watcher, checks) = watch(&[true]);
+        wait_for_checks(&checks, 5);
+        assert_eq!(watcher.events(), [GameProcessEvent::Started]);
+        // Mientras sigue abierto no se vuelve a avisar
+        wait_for_checks(&checks, 10);
+        assert!(watcher.events().is_empty());
+    }
+
+    #[test]
+    fn stops_when_dropped() {
+        let (watcher, checks) = watch(&[false]);
+        wait_for_checks(&checks, 2);
+        drop(watcher);
+        thread::sleep(Duration::from_millis(50));
+        let after_stop = checks.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(checks.load(Ordering::SeqCst), after_stop);
+    }
+}
@@ -0,0 +1,246 @@
+use std::time::Duration;
+
+use super::constants::*;
+use super::outcomes::{RoundContext, Score};
+
+// Volúmenes por defecto de cada estado, indexados por el valor del estado
+const DEFAULT_VOLUMES: [u8; STATE_COUNT] = [100, 50, 0, 100, 100, 100, 100, 100];
+// Duración máxima del fundido entre estados, la misma en la interfaz y al importar los ajustes
+pub const MAX_FADE_DURATION_MS: u32 = 5000;
+
+// Configuración con nombre que el usuario puede cambiar rápidamente (ej. "Ranked - silent", "Deathmatch")
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub volumes: Vec<u8>, // Volumen de cada estado en %, indexado por el valor del estado
+    pub relative_mode: bool,
+    pub fade_duration_ms: u32,
+    pub pause_on_zero: bool, // Pausar la reproducción al llegar a un estado con volumen 0
+    pub process_name: Option<String>, // Proceso que se selecciona al elegir el perfil
+    pub min_dwell_ms: Vec<u32>, // Tiempo que se tiene que mantener cada estado detectado para aplicar su volumen
+    pub early_fade_s: u32, // Segundos antes del final previsto de la fase de compra en los que empieza el fundido; 0 para no adelantarlo
+    pub round_rules: Vec<RoundRule>,
+}
+
+// Volumen especial en un momento de la partida (ej. silencio en el punto de partido). Se usa la primera regla que se
+// cumple en lugar del volumen del estado.
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RoundRule {
+    pub enabled: bool,
+    pub when: RoundContext,
+    pub state: Option<u8>, // Estado de la partida en el que se aplica; None para cualquiera
+    pub volume: u8,
+}
+
+impl Default for RoundRule {
+    fn default() -> Self {
+        Self { enabled: true, when: RoundContext::HalfTime, state: Some(States::IN_GAME_PREPARING), volume: 100 }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_owned(),
+            volumes: DEFAULT_VOLUMES.to_vec(),
+            relative_mode: false,
+            fade_duration_ms: 1000,
+            pause_on_zero: true,
+            process_name: None,
+            min_dwell_ms: vec![0; STATE_COUNT],
+            early_fade_s: 0,
+            round_rules: Vec::new(),
+        }
+    }
+}
+
+impl Profile {
+    pub fn volume(&self, state: u8) -> u8 {
+        self.volumes.get(state as usize).copied().unwrap_or(DEFAULT_VOLUMES[0])
+    }
+
+    pub fn min_dwell(&self, state: u8) -> Duration {
+        Duration::from_millis(self.min_dwell_ms.get(state as usize).copied().unwrap_or(0) as u64)
+    }
+
+    // Volumen de la primera regla de ronda que se cumple en el estado dado, si hay alguna
+    pub fn round_volume(&self, state: u8, round: Option<u32>, score: &Score, mode: Option<GameMode>) -> Option<u8> {
+        let round = round.filter(|_| States::in_match(state))?;
+        self.round_rules.iter()
+            .find(|rule| rule.enabled && rule.state.map_or(true, |rule_state| rule_state == state) && rule.when.applies(round, score, mode))
+            .map(|rule| rule.volume)
+    }
+
+    // Se asegura de que haya un volumen por cada estado, por si se cargó un perfil de una versión con menos estados
+    pub fn normalize(&mut self) {
+        let old_len = self.volumes.len();
+        if old_len < STATE_COUNT {
+            self.volumes.extend_from_slice(&DEFAULT_VOLUMES[old_len..]);
+            // Los estados previos a la partida formaban parte de "No en partida", así que usan su volumen
+            if old_len > States::NOT_IN_GAME as usize {
+                for state in (old_len..STATE_COUNT).filter(|state| States::is_pregame(*state as u8)) {
+                    self.volumes[state] = self.volumes[States::NOT_IN_GAME as usize];
+                }
+            }
+        }
+        self.volumes.truncate(STATE_COUNT);
+        self.min_dwell_ms.resize(STATE_COUNT, 0);
+        self.round_rules.retain(|rule| rule.state.map_or(true, States::in_match));
+        for rule in self.round_rules.iter_mut() {
+            rule.volume = rule.volume.min(100);
+        }
+        for volume in self.volumes.iter_mut() {
+            *volume = (*volume).min(100);
+        }
+        // El volumen fuera de partida no puede ser 0 porque la música se quedaría pausada al terminar
+        self.volumes[States::NOT_IN_GAME as usize] = self.volumes[States::NOT_IN_GAME as usize].max(1);
+    }
+}
+
+// Nombre que no coincide con el de ningún perfil existente, añadiendo un número si es necesario
+pub fn unique_name(profiles: &[Profile], base: &str) -> String {
+    let exists = |name: &str| profiles.iter().any(|profile| profile.name == name);
+    if !exists(base) { return base.to_owned(); }
+
+    let mut index = 2;
+    while exists(&format!("{} {}", base, index)) { index += 1; }
+    format!("{} {}", base, index)
+}
+
+// Comprueba el nombre de un perfil al renombrarlo; las reglas y los eventos identifican los perfiles por su nombre
+pub fn check_name(profiles: &[Profile], index: usize, name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("The name of the profile can't be empty".to_owned());
+    }
+    if profiles.iter().enumerate().any(|(other, profile)| other != index && profile.name == name) {
+        return Err(format!("There is another profile named \"{}\"", name));
+    }
+    Ok(())
+}
+
+// Regla para elegir un perfil automáticamente al empezar una partida; None en el modo o el mapa significa cualquiera
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<GameMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<String>, // Nombre del mapa en el juego (ej. "Split", no "Bonsai")
+    pub profile: String,
+}
+
+impl ProfileRule {
+    fn matches(&self, mode: Option<GameMode>, map: Option<&str>) -> bool {
+        (self.mode.is_none() || self.mode == mode) && (self.map.is_none() || self.map.as_deref() == map)
+    }
+}
+
+// Perfiles que recibe el hilo del controlador: el elegido por el usuario y los que se pueden elegir automáticamente
+#[derive(Clone)]
+pub struct ProfileSet {
+    pub profiles: Vec<Profile>,
+    pub selected: usize,
+    pub rules: Vec<ProfileRule>,
+}
+
+impl ProfileSet {
+    pub fn selected(&self) -> &Profile {
+        &self.profiles[self.selected]
+    }
+
+    // Perfil de la primera regla que coincide con la partida; si ninguna coincide, el elegido por el usuario
+    pub fn for_match(&self, mode: Option<GameMode>, map: Option<&str>) -> &Profile {
+        self.rules.iter()
+            .filter(|rule| rule.matches(mode, map))
+            .find_map(|rule| self.profiles.iter().find(|profile| profile.name == rule.profile))
+            .unwrap_or_else(|| self.selected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str) -> Profile {
+        Profile { name: name.to_owned(), ..Default::default() }
+    }
+
+    fn rule(mode: Option<GameMode>, map: Option<&str>, profile: &str) -> ProfileRule {
+        ProfileRule { mode, map: map.map(str::to_owned), profile: profile.to_owned() }
+    }
+
+    #[test]
+    fn profile_for_match() {
+        let set = ProfileSet {
+            profiles: vec![profile("Default"), profile("Ranked"), profile("Ranked on Split"), profile("Deathmatch")],
+            selected: 0,
+            rules: vec![
+                rule(Some(GameMode::Competitive), Some("Split"), "Ranked on Split"),
+                rule(Some(GameMode::Competitive), None, "Ranked"),
+                rule(Some(GameMode::Deathmatch), None, "Deleted profile"),
+                rule(None, Some("Lotus"), "Deathmatch"),
+            ],
+        };
+        let name = |mode, map| set.for_match(mode, map).name.as_str();
+
+        // Se usa la primera regla que coincide
+        assert_eq!(name(Some(GameMode::Competitive), Some("Split")), "Ranked on Split");
+        assert_eq!(name(Some(GameMode::Competitive), Some("Ascent")), "Ranked");
+        assert_eq!(name(Some(GameMode::Competitive), None), "Ranked");
+        // Las reglas sin modo valen para cualquiera, también si no se ha detectado
+        assert_eq!(name(None, Some("Lotus")), "Deathmatch");
+        // Una regla con un perfil que ya no existe se salta
+        assert_eq!(name(Some(GameMode::Deathmatch), Some("Ascent")), "Default");
+        assert_eq!(name(None, None), "Default");
+    }
+
+    #[test]
+    fn normalize_profile_of_an_older_version() {
+        // Perfil de la versión con solo tres estados: fuera de partida, fase de compra y jugando
+        let mut profile = Profile { volumes: vec![80, 30, 0], min_dwell_ms: Vec::new(), ..Default::default() };
+        profile.normalize();
+
+        assert_eq!(profile.volumes.len(), STATE_COUNT);
+        assert_eq!(profile.min_dwell_ms, vec![0; STATE_COUNT]);
+        for state in States::ALL.into_iter().filter(|state| States::is_pregame(*state)) {
+            assert_eq!(profile.volume(state), 80, "{}", States::name(state));
+        }
+        assert_eq!((profile.volume(States::IN_GAME_PREPARING), profile.volume(States::IN_GAME_PLAYING)), (30, 0));
+    }
+
+    #[test]
+    fn normalize_invalid_values() {
+        let mut profile = Profile { volumes: vec![0; STATE_COUNT + 2], ..Default::default() };
+        profile.volumes[States::IN_GAME_PLAYING as usize] = 150;
+        profile.round_rules = vec![
+            RoundRule { volume: 200, ..Default::default() },
+            RoundRule { state: Some(States::MENUS), ..Default::default() },
+            RoundRule { state: None, ..Default::default() },
+        ];
+        profile.normalize();
+
+        assert_eq!(profile.volumes.len(), STATE_COUNT);
+        // Fuera de partida no puede ser 0 porque la música se quedaría pausada
+        assert_eq!(profile.volume(States::NOT_IN_GAME), 1);
+        assert_eq!(profile.volume(States::IN_GAME_PLAYING), 100);
+        // Las reglas de ronda solo se aplican en los estados de la partida
+        assert_eq!(profile.round_rules.len(), 2);
+        assert_eq!(profile.round_rules[0].volume, 100);
+        assert_eq!(profile.round_rules[1].state, None);
+    }
+
+    #[test]
+    fn profile_names() {
+        let profiles = [profile("Default"), profile("Ranked"), profile("New profile"), profile("New profile 2")];
+        assert_eq!(unique_name(&profiles, "Deathmatch"), "Deathmatch");
+        assert_eq!(unique_name(&profiles, "New profile"), "New profile 3");
+
+        assert!(check_name(&profiles, 1, "Ranked").is_ok());
+        assert!(check_name(&profiles, 1, "Ranked - silent").is_ok());
+        assert_eq!(check_name(&profiles, 1, "Default"), Err("There is another profile named \"Default\"".to_owned()));
+        assert!(check_name(&profiles, 1, "").is_err());
+        assert!(check_name(&profiles, 1, "  ").is_err());
+    }
+}
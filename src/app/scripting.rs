@@ -0,0 +1,293 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, INT};
+
+use super::constants::*;
+use super::hooks::{self, HookConfig};
+
+const SCRIPT_FILE: &str = "script.rhai";
+
+// Límites para que un script no pueda bloquear el controlador (ej. con un bucle infinito) ni llenar la memoria
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 64 * 1024;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+// Script con el que se crea el archivo la primera vez que se abre el editor
+pub const TEMPLATE: &str = r#"// Custom logic for Valorant Music Controller (Rhai script, https://rhai.rs/book/)
+// Save the script to apply the changes, even while the program is active.
+//
+// Available functions:
+//   emit(name)            Send a custom event (shown in the status panel and sent to the API clients)
+//   set_state(state)      Change the state, only in on_log_line. States: "not_in_game", "menus", "in_queue",
+//                         "match_found", "agent_select", "loading", "buy_phase" and "playing"
+//   set_volume(level)     Use this volume (0-100) in every state until reset_volume() is called
+//   reset_volume()        Go back to the volumes of the profile
+//   pause() / resume()    Pause or resume the media player
+//   run(command)          Run a command, with the timeout and limits of the hooks (only if hooks are enabled)
+//   print(text)           Write a message to the log viewer
+
+// Called for every line of the game log, before the program analyzes it
+fn on_log_line(line) {
+    // if line.contains("LogShooterGameState: Match Ended") { emit("match_over"); }
+}
+
+// Called when the state changes. ctx has: round, map, mode, profile, volume, suspended
+fn on_state_change(prev, next, ctx) {
+    // if next == "playing" && ctx.mode == "Deathmatch" { set_volume(10); }
+}
+"#;
+
+// Acciones que pide el script; las aplica el controlador al terminar cada llamada
+#[derive(Debug)]
+pub enum ScriptAction {
+    Emit(String),
+    SetState(u8),
+    SetVolume(Option<u8>),
+    Pause,
+    Resume,
+    Run(String), // La ejecuta el propio script, no se retorna al controlador
+}
+
+// Último error del script (de compilación o de ejecución), para mostrarlo en el editor
+static LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn last_error() -> Option<String> {
+    LAST_ERROR.lock().unwrap().clone()
+}
+
+fn set_error(error: Option<String>) {
+    if let Some(error) = &error { log::error!("Script error: {}", error); }
+    *LAST_ERROR.lock().unwrap() = error;
+}
+
+pub fn path() -> Option<PathBuf> {
+    super::data_path(SCRIPT_FILE)
+}
+
+// Comprueba si el código compila, sin ejecutarlo
+pub fn check(source: &str) -> Result<(), String> {
+    sandboxed_engine().compile(source).map(|_| ()).map_err(|err| err.to_string())
+}
+
+// Motor sin acceso al sistema: Rhai no permite leer archivos ni usar la red, y se limitan los recursos
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        .disable_symbol("eval");
+    engine.on_print(|text| log::info!("Script: {}", text));
+    engine.on_debug(|text, _, position| log::debug!("Script ({}): {}", position, text));
+    engine
+}
+
+// Script del usuario cargado en el controlador. Se vuelve a cargar cuando cambia el archivo.
+pub struct Script {
+    engine: Engine,
+    scope: Scope<'static>,
+    ast: Option<AST>,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    actions: Arc<Mutex<Vec<ScriptAction>>>,
+    hook_config: HookConfig,
+}
+
+impl Script {
+    pub fn load(hook_config: HookConfig) -> Option<Self> {
+        Some(Self::load_from(path()?, hook_config))
+    }
+
+    fn load_from(path: PathBuf, hook_config: HookConfig) -> Self {
+        let actions: Arc<Mutex<Vec<ScriptAction>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = sandboxed_engine();
+
+        let push = |actions: &Arc<Mutex<Vec<ScriptAction>>>| {
+            let actions = actions.clone();
+            move |action: ScriptAction| actions.lock().unwrap().push(action)
+        };
+        let emit = push(&actions);
+        engine.register_fn("emit", move |name: &str| emit(ScriptAction::Emit(name.to_owned())));
+        let set_state = push(&actions);
+        engine.register_fn("set_state", move |key: &str| -> Result<(), Box<EvalAltResult>> {
+            let state = (0..STATE_COUNT as u8).find(|state| States::key(*state) == key).ok_or_else(|| format!("Unknown state \"{}\"", key))?;
+            set_state(ScriptAction::SetState(state));
+            Ok(())
+        });
+        let set_volume = push(&actions);
+        engine.register_fn("set_volume", move |level: INT| set_volume(ScriptAction::SetVolume(Some(level.clamp(0, 100) as u8))));
+        let reset_volume = push(&actions);
+        engine.register_fn("reset_volume", move || reset_volume(ScriptAction::SetVolume(None)));
+        let pause = push(&actions);
+        engine.register_fn("pause", move || pause(ScriptAction::Pause));
+        let resume = push(&actions);
+        engine.register_fn("resume", move || resume(ScriptAction::Resume));
+        let run = push(&actions);
+        engine.register_fn("run", move |command: &str| run(ScriptAction::Run(command.to_owned())));
+
+        let mut script = Self { engine, scope: Scope::new(), ast: None, path, modified: None, actions, hook_config };
+        script.reload_if_changed();
+        script
+    }
+
+    // Compila y ejecuta el código global del script si el archivo ha cambiado desde la última carga
+    pub fn reload_if_changed(&mut self) {
+        let modified = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+        if modified == self.modified { return; }
+        self.modified = modified;
+        self.ast = None;
+        self.scope = Scope::new();
+        if modified.is_none() { return; } // Sin archivo no hay script
+
+        let result = std::fs::read_to_string(&self.path).map_err(|err| err.to_string())
+            .and_then(|source| self.engine.compile(source).map_err(|err| err.to_string()))
+            .and_then(|ast| self.engine.run_ast_with_scope(&mut self.scope, &ast).map(|_| ast).map_err(|err| err.to_string()));
+        match result {
+            Ok(ast) => {
+                log::info!("Script loaded from {}", self.path.display());
+                self.ast = Some(ast);
+                set_error(None);
+            }
+            Err(err) => set_error(Some(err)),
+        }
+        self.actions.lock().unwrap().clear(); // Las acciones del código global no se aplican
+    }
+
+    pub fn on_log_line(&mut self, line: &str) -> Vec<ScriptAction> {
+        self.call("on_log_line", vec![line.into()])
+    }
+
+    pub fn on_state_change(&mut self, prev: u8, next: u8, context: Map) -> Vec<ScriptAction> {
+        self.call("on_state_change", vec![States::key(prev).into(), States::key(next).into(), context.into()])
+    }
+
+    // Llama a una función del script si existe. Un error desactiva el script hasta que se vuelva a guardar, para no
+    // repetir el mismo error con cada línea del log.
+    fn call(&mut self, name: &str, args: Vec<Dynamic>) -> Vec<ScriptAction> {
+        let Some(ast) = &self.ast else { return Vec::new(); };
+        if !ast.iter_functions().any(|function| function.name == name && function.params.len() == args.len()) { return Vec::new(); }
+
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(false);
+        if let Err(err) = self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, ast, name, args) {
+            set_error(Some(format!("{}: {}", name, err)));
+            self.ast = None;
+        }
+
+        let mut actions = std::mem::take(&mut *self.actions.lock().unwrap());
+        actions.retain(|action| match action {
+            // run() ejecuta comandos como los hooks, así que solo se permite con los hooks activados
+            ScriptAction::Run(command) if !self.hook_config.enabled => {
+                log::warn!("Script command not run because hooks are disabled: {}", command);
+                false
+            }
+            ScriptAction::Run(command) => {
+                hooks::run(command.clone(), vec![("VMC_EVENT", "script".to_owned())], &self.hook_config);
+                false
+            }
+            _ => true,
+        });
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vmc-test-{}-{}", std::process::id(), name))
+    }
+
+    fn emitted(actions: &[ScriptAction]) -> Vec<&str> {
+        actions.iter().filter_map(|action| match action { ScriptAction::Emit(name) => Some(name.as_str()), _ => None }).collect()
+    }
+
+    // Escribe el script con la fecha de modificación indicada, para no depender de la precisión del sistema de archivos
+    fn write_script(path: &PathBuf, source: &str, modified: SystemTime) {
+        std::fs::write(path, source).unwrap();
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn sandbox_limits() {
+        let engine = sandboxed_engine();
+        let error = |source: &str| *engine.run(source).err().unwrap();
+
+        assert!(matches!(error("loop {}"), EvalAltResult::ErrorTooManyOperations(..)));
+        assert!(matches!(error("fn f(n) { f(n + 1) } f(0)"), EvalAltResult::ErrorStackOverflow(..)));
+        assert!(matches!(error("let text = \"0123456789\"; loop { text += text; }"), EvalAltResult::ErrorDataTooLarge(..)));
+        assert!(matches!(error("let list = []; loop { list.push(1); }"), EvalAltResult::ErrorDataTooLarge(..)));
+        // eval no se puede usar para ejecutar código que no pasa por el editor
+        assert!(check("eval(\"emit(1)\")").is_err());
+        assert!(check("let volume = 10;").is_ok());
+    }
+
+    #[test]
+    fn reload_when_the_file_changes() {
+        let path = temp_path("script.rhai");
+        let saved = SystemTime::now() - Duration::from_secs(60);
+        write_script(&path, "fn on_log_line(line) { emit(\"first\"); }", saved);
+        let mut script = Script::load_from(path.clone(), HookConfig::default());
+        assert_eq!(emitted(&script.on_log_line("line")), ["first"]);
+
+        // Solo se vuelve a cargar si cambia la fecha de modificación
+        write_script(&path, "fn on_log_line(line) { emit(\"second\"); }", saved);
+        script.reload_if_changed();
+        assert_eq!(emitted(&script.on_log_line("line")), ["first"]);
+
+        write_script(&path, "fn on_log_line(line) { emit(\"second\"); }", saved + Duration::from_secs(1));
+        script.reload_if_changed();
+        assert_eq!(emitted(&script.on_log_line("line")), ["second"]);
+
+        // Un error de ejecución desactiva el script hasta que se vuelve a guardar
+        write_script(&path, "fn on_log_line(line) { emit(\"third\"); loop {} }", saved + Duration::from_secs(2));
+        script.reload_if_changed();
+        assert_eq!(emitted(&script.on_log_line("line")), ["third"]);
+        assert!(script.on_log_line("line").is_empty());
+
+        // Sin archivo no hay script
+        std::fs::remove_file(&path).unwrap();
+        script.reload_if_changed();
+        assert!(script.ast.is_none());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn run_only_with_hooks_enabled() {
+        let path = temp_path("run-script.rhai");
+        let marker = temp_path("run-script-marker");
+        let source = format!("fn on_log_line(line) {{ run(\"touch '{}'\"); emit(\"done\"); }}", marker.display());
+        write_script(&path, &source, SystemTime::now());
+        let created = || {
+            let start = std::time::Instant::now();
+            while start.elapsed() < Duration::from_secs(5) {
+                if marker.exists() { return true; }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            false
+        };
+
+        // Con los hooks desactivados el comando se descarta, pero el resto de acciones se aplican
+        let mut script = Script::load_from(path.clone(), HookConfig::default());
+        let actions = script.on_log_line("line");
+        assert_eq!(emitted(&actions), ["done"]);
+        assert_eq!(actions.len(), 1);
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(!marker.exists());
+
+        // Con los hooks activados se ejecuta, y tampoco se retorna al controlador
+        let mut script = Script::load_from(path.clone(), HookConfig { enabled: true, ..Default::default() });
+        assert_eq!(script.on_log_line("line").len(), 1);
+        assert!(created());
+
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&marker);
+    }
+}
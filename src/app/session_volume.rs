@@ -0,0 +1,111 @@
+use windows::core::{implement, Interface, GUID, PCWSTR};
+use windows::Win32::Foundation::{CloseHandle, BOOL};
+use windows::Win32::Media::Audio::{
+    eMultimedia, eRender, AudioSessionDisconnectReason, AudioSessionState, IAudioSessionControl, IAudioSessionControl2,
+    IAudioSessionEvents, IAudioSessionEvents_Impl, IAudioSessionManager2, IMMDeviceEnumerator, ISimpleAudioVolume, MMDeviceEnumerator,
+};
+use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+use windows::Win32::System::ProcessStatus::K32GetProcessImageFileNameA;
+use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+use super::commands::{self, ControllerCommand};
+
+// Contexto con el que el programa cambia el volumen, para distinguir sus cambios de los del usuario en las notificaciones
+const EVENT_CONTEXT: GUID = GUID::from_u128(0x6f1c_2a4e_93b7_4d0a_8e52_c7a1_0b3d_9f64);
+
+// Sesiones de audio de un proceso. Se registra IAudioSessionEvents en cada una para que los cambios de volumen que haga
+// el usuario lleguen al controlador como ControllerCommand::SessionVolumeChanged en cuanto se producen.
+// COM ya está inicializado en el hilo del controlador (AudioController::init).
+pub struct SessionVolume {
+    sessions: Vec<(IAudioSessionControl, ISimpleAudioVolume, IAudioSessionEvents)>,
+}
+
+impl SessionVolume {
+    // None si el proceso no tiene ninguna sesión de audio
+    pub fn open(process_name: &str) -> Option<Self> {
+        let sessions = unsafe { process_sessions(process_name) }
+            .map_err(|err| log::warn!("Couldn't get the audio sessions of {}: {}", process_name, err))
+            .ok()?;
+
+        let sessions: Vec<_> = sessions.into_iter().filter_map(|control| unsafe {
+            let volume: ISimpleAudioVolume = control.cast().ok()?;
+            let events: IAudioSessionEvents = SessionEvents.into();
+            match control.RegisterAudioSessionNotification(&events) {
+                Ok(()) => Some((control, volume, events)),
+                Err(err) => {
+                    log::warn!("Couldn't listen to the volume changes of {}: {}", process_name, err);
+                    None
+                }
+            }
+        }).collect();
+        (!sessions.is_empty()).then_some(Self { sessions })
+    }
+
+    // Volumen actual de la primera sesión del proceso
+    pub fn volume(&self) -> Option<f32> {
+        let (_, simple_volume, _) = self.sessions.first()?;
+        unsafe { simple_volume.GetMasterVolume() }.ok()
+    }
+
+    // Cambia el volumen sin que se notifique como un cambio del usuario
+    pub fn set(&self, volume: f32) {
+        for (_, simple_volume, _) in &self.sessions {
+            if let Err(err) = unsafe { simple_volume.SetMasterVolume(volume, &EVENT_CONTEXT) } {
+                log::warn!("Couldn't set the volume: {}", err);
+            }
+        }
+    }
+}
+
+impl Drop for SessionVolume {
+    fn drop(&mut self) {
+        for (control, _, events) in &self.sessions {
+            let _ = unsafe { control.UnregisterAudioSessionNotification(events) };
+        }
+    }
+}
+
+// Sesiones del dispositivo de salida por defecto cuyo ejecutable se llama como el proceso (sin ".exe", igual que en AudioController)
+unsafe fn process_sessions(process_name: &str) -> windows::core::Result<Vec<IAudioSessionControl>> {
+    let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_INPROC_SERVER)?;
+    let manager: IAudioSessionManager2 = enumerator.GetDefaultAudioEndpoint(eRender, eMultimedia)?.Activate(CLSCTX_INPROC_SERVER, None)?;
+    let sessions = manager.GetSessionEnumerator()?;
+
+    let mut found = Vec::new();
+    for index in 0..sessions.GetCount()? {
+        let Ok(control) = sessions.GetSession(index) else { continue; };
+        let Some(pid) = control.cast::<IAudioSessionControl2>().and_then(|control| control.GetProcessId()).ok().filter(|pid| *pid != 0) else { continue; };
+        if process_file_name(pid).is_some_and(|name| name == process_name) { found.push(control); }
+    }
+    Ok(found)
+}
+
+unsafe fn process_file_name(pid: u32) -> Option<String> {
+    let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+    let mut path = [0u8; 260];
+    let length = K32GetProcessImageFileNameA(process, &mut path) as usize;
+    CloseHandle(process);
+    let path = String::from_utf8_lossy(&path[..length]).into_owned();
+    path.rsplit('\\').next().map(|file| file.replace(".exe", ""))
+}
+
+#[implement(IAudioSessionEvents)]
+struct SessionEvents;
+
+#[allow(non_snake_case)]
+impl IAudioSessionEvents_Impl for SessionEvents {
+    fn OnSimpleVolumeChanged(&self, newvolume: f32, _newmute: BOOL, eventcontext: *const GUID) -> windows::core::Result<()> {
+        // Los cambios hechos por el programa se ignoran
+        if eventcontext.is_null() || unsafe { *eventcontext } != EVENT_CONTEXT {
+            commands::send(ControllerCommand::SessionVolumeChanged(newvolume));
+        }
+        Ok(())
+    }
+
+    fn OnDisplayNameChanged(&self, _newdisplayname: &PCWSTR, _eventcontext: *const GUID) -> windows::core::Result<()> { Ok(()) }
+    fn OnIconPathChanged(&self, _newiconpath: &PCWSTR, _eventcontext: *const GUID) -> windows::core::Result<()> { Ok(()) }
+    fn OnChannelVolumeChanged(&self, _channelcount: u32, _newchannelvolumearray: *const f32, _changedchannel: u32, _eventcontext: *const GUID) -> windows::core::Result<()> { Ok(()) }
+    fn OnGroupingParamChanged(&self, _newgroupingparam: *const GUID, _eventcontext: *const GUID) -> windows::core::Result<()> { Ok(()) }
+    fn OnStateChanged(&self, _newstate: AudioSessionState) -> windows::core::Result<()> { Ok(()) }
+    fn OnSessionDisconnected(&self, _disconnectreason: AudioSessionDisconnectReason) -> windows::core::Result<()> { Ok(()) }
+}
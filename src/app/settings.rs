@@ -0,0 +1,438 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::constants::*;
+use super::outcomes::RoundContext;
+use super::profiles::{Profile, ProfileRule, RoundRule, MAX_FADE_DURATION_MS};
+use super::sources::SourceKind;
+
+// Versión actual del formato del archivo. Si cambia el formato se sube la versión y se añade un paso a migrate()
+const CURRENT_VERSION: i64 = 2;
+
+// Cabecera que se escribe al exportar para que el archivo se pueda editar a mano
+const HEADER: &str = "\
+# Valorant Music Controller settings
+#
+# version            Format version of this file, do not change it.
+# selected_profile   Name of the profile selected when the file is imported.
+# log_to_file        Write the log of the program to a file.
+# log_path           Path of the log file. Leave it empty to use the default one.
+# minimize_to_tray   Minimizing the window hides it in the system tray (Windows only). Optional, true if missing.
+# close_to_tray      Closing the window hides it in the system tray (Windows only).
+# start_minimized    Start hidden in the system tray (Windows only).
+# game_state_source  Where the state of the game is read from: log (the game log), riot_api (the local API of the
+#                    Riot Client) or automatic (the Riot Client API if available, the game log otherwise). Optional,
+#                    log if missing.
+#
+# [[profiles]]       One section for each profile:
+#   name             Unique name of the profile.
+#   process_name     Process selected with the profile (optional).
+#   relative_mode    Volumes are a percentage of the process volume.
+#   fade_duration_ms Duration of the volume fade between states, from 0 to {MAX_FADE_DURATION_MS}.
+#   pause_on_zero    Pause the media player in states with 0% volume.
+#   early_fade_s     Start fading to the playing volume this many seconds before the buy phase is expected to end,
+#                    from 0 (disabled) to 10.
+#   [profiles.volumes]
+#   not_in_game      Volume outside of a match, from 1 to 100.
+#   buy_phase        Volume during the buy phase, from 0 to 100.
+#   playing          Volume while playing a round, from 0 to 100.
+#   menus            Volume in the menus of the game, from 0 to 100.
+#   in_queue         Volume while searching for a match, from 0 to 100.
+#   match_found      Volume when a match has been found, from 0 to 100.
+#   agent_select     Volume while selecting an agent, from 0 to 100.
+#   loading          Volume while the match is loading, from 0 to 100.
+#   [profiles.min_dwell_ms]
+#   <state>          Time in milliseconds a detected state must last before its volume is applied, from 0 to 10000
+#                    (optional, 0 if missing). Uses the same names as [profiles.volumes].
+#   [[profiles.round_rules]] Special volume at some moments of the match, the first matching rule is used (optional):
+#   when             half_time (first round after the side swap), match_point or overtime.
+#   state            buy_phase or playing (optional, any of them if missing).
+#   volume           Volume used instead of the one of the state, from 0 to 100.
+#   enabled          Use the rule (optional, true if missing).
+#
+# [[rules]]          Profile used automatically when a match starts, the first matching rule is used:
+#   mode             Competitive, Unrated, Deathmatch, SpikeRush, Custom or Range (optional, any mode if missing).
+#   map              Name of the map, e.g. \"Ascent\" (optional, any map if missing).
+#   profile          Name of the profile to use.
+
+";
+
+// Ajustes que se pueden importar y exportar
+pub struct Settings {
+    pub profiles: Vec<Profile>,
+    pub selected_profile: usize,
+    pub rules: Vec<ProfileRule>,
+    pub log_to_file: bool,
+    pub log_path: String,
+    pub minimize_to_tray: bool,
+    pub close_to_tray: bool,
+    pub start_minimized: bool,
+    pub game_state_source: SourceKind,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsFile {
+    version: i64,
+    selected_profile: String,
+    #[serde(default)]
+    log_to_file: bool,
+    #[serde(default)]
+    log_path: String,
+    #[serde(default = "default_enabled")]
+    minimize_to_tray: bool,
+    #[serde(default)]
+    close_to_tray: bool,
+    #[serde(default)]
+    start_minimized: bool,
+    #[serde(default)]
+    game_state_source: SourceKind,
+    profiles: Vec<ProfileEntry>,
+    #[serde(default)]
+    rules: Vec<ProfileRule>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileEntry {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    process_name: Option<String>,
+    #[serde(default)]
+    relative_mode: bool,
+    #[serde(default = "default_fade_duration")]
+    fade_duration_ms: i64,
+    #[serde(default = "default_pause_on_zero")]
+    pause_on_zero: bool,
+    #[serde(default)]
+    early_fade_s: i64,
+    volumes: BTreeMap<String, i64>, // Volumen de cada estado, por su identificador (States::key)
+    #[serde(default)]
+    min_dwell_ms: BTreeMap<String, i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    round_rules: Vec<RoundRuleEntry>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct RoundRuleEntry {
+    when: RoundContext,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    volume: i64,
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    enabled: bool,
+}
+
+fn default_enabled() -> bool { true }
+fn is_enabled(enabled: &bool) -> bool { *enabled }
+
+fn default_fade_duration() -> i64 { Profile::default().fade_duration_ms as i64 }
+fn default_pause_on_zero() -> bool { Profile::default().pause_on_zero }
+
+pub fn export(settings: &Settings, path: &Path) -> Result<(), String> {
+    let file = SettingsFile {
+        version: CURRENT_VERSION,
+        selected_profile: settings.profiles[settings.selected_profile].name.clone(),
+        log_to_file: settings.log_to_file,
+        log_path: settings.log_path.clone(),
+        minimize_to_tray: settings.minimize_to_tray,
+        close_to_tray: settings.close_to_tray,
+        start_minimized: settings.start_minimized,
+        game_state_source: settings.game_state_source,
+        profiles: settings.profiles.iter().map(|profile| ProfileEntry {
+            name: profile.name.clone(),
+            process_name: profile.process_name.clone(),
+            relative_mode: profile.relative_mode,
+            fade_duration_ms: profile.fade_duration_ms as i64,
+            pause_on_zero: profile.pause_on_zero,
+            early_fade_s: profile.early_fade_s as i64,
+            volumes: (0..STATE_COUNT).map(|state| (States::key(state as u8).to_owned(), profile.volume(state as u8) as i64)).collect(),
+            min_dwell_ms: (0..STATE_COUNT).map(|state| (States::key(state as u8).to_owned(), profile.min_dwell(state as u8).as_millis() as i64)).collect(),
+            round_rules: profile.round_rules.iter().map(|rule| RoundRuleEntry {
+                when: rule.when,
+                state: rule.state.map(|state| States::key(state).to_owned()),
+                volume: rule.volume as i64,
+                enabled: rule.enabled,
+            }).collect(),
+        }).collect(),
+        rules: settings.rules.clone(),
+    };
+
+    let contents = toml::to_string_pretty(&file).map_err(|err| format!("Couldn't write the settings: {}", err))?;
+    let header = HEADER.replace("{MAX_FADE_DURATION_MS}", &MAX_FADE_DURATION_MS.to_string());
+    std::fs::write(path, format!("{}{}", header, contents)).map_err(|err| format!("Couldn't write {}: {}", path.display(), err))
+}
+
+pub fn import(path: &Path) -> Result<Settings, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| format!("Couldn't read {}: {}", path.display(), err))?;
+    let table: toml::Table = contents.parse().map_err(|err| format!("{} is not a valid TOML file:\n{}", path.display(), err))?;
+
+    // Los archivos de la versión actual se leen directamente del texto para que los errores indiquen la línea
+    let file: SettingsFile = if table.get("version").and_then(|version| version.as_integer()) == Some(CURRENT_VERSION) {
+        toml::from_str(&contents)
+    }
+    else {
+        migrate(table)?.try_into()
+    }.map_err(|err| format!("Invalid settings file:\n{}", err))?;
+    validate(file)
+}
+
+// Convierte los archivos de versiones anteriores al formato actual, versión a versión
+fn migrate(mut table: toml::Table) -> Result<toml::Table, String> {
+    let version = match table.get("version") {
+        None => return Err("Missing \"version\"".to_owned()),
+        Some(toml::Value::Integer(version)) => *version,
+        Some(value) => return Err(format!("\"version\" must be an integer, found {}", value.type_str())),
+    };
+    if version > CURRENT_VERSION {
+        return Err(format!("The settings file has version {}, but this version of the program only supports up to version {}. Update the program to import it.", version, CURRENT_VERSION));
+    }
+    if version < 1 {
+        return Err(format!("Unknown settings file version {}", version));
+    }
+
+    if version < 2 {
+        // Versión 1: los estados previos a la partida formaban parte de "No en partida", así que usan su volumen
+        if let Some(toml::Value::Array(profiles)) = table.get_mut("profiles") {
+            for volumes in profiles.iter_mut().filter_map(|profile| profile.get_mut("volumes")).filter_map(|volumes| volumes.as_table_mut()) {
+                let Some(not_in_game) = volumes.get(States::key(States::NOT_IN_GAME)).cloned() else { continue; };
+                for state in States::ALL.into_iter().filter(|state| States::is_pregame(*state)) {
+                    volumes.entry(States::key(state)).or_insert_with(|| not_in_game.clone());
+                }
+            }
+        }
+        log::info!("Migrated the settings file from version 1 to 2");
+    }
+
+    table.insert("version".to_owned(), CURRENT_VERSION.into());
+    Ok(table)
+}
+
+fn validate(file: SettingsFile) -> Result<Settings, String> {
+    if file.profiles.is_empty() {
+        return Err("There must be at least one profile".to_owned());
+    }
+
+    let mut profiles: Vec<Profile> = Vec::new();
+    for (index, entry) in file.profiles.into_iter().enumerate() {
+        let context = format!("profiles[{}] (\"{}\")", index, entry.name);
+        if entry.name.trim().is_empty() {
+            return Err(format!("profiles[{}]: the name can't be empty", index));
+        }
+        if profiles.iter().any(|profile| profile.name == entry.name) {
+            return Err(format!("{}: there is another profile with the same name", context));
+        }
+        if !(0..=MAX_FADE_DURATION_MS as i64).contains(&entry.fade_duration_ms) {
+            return Err(format!("{}: fade_duration_ms is {}, it must be between 0 and {}", context, entry.fade_duration_ms, MAX_FADE_DURATION_MS));
+        }
+        if !(0..=10).contains(&entry.early_fade_s) {
+            return Err(format!("{}: early_fade_s is {}, it must be between 0 and 10", context, entry.early_fade_s));
+        }
+
+        let mut volumes = Vec::new();
+        for state in 0..STATE_COUNT as u8 {
+            let key = States::key(state);
+            let min = if state == States::NOT_IN_GAME { 1 } else { 0 };
+            match entry.volumes.get(key) {
+                None => return Err(format!("{}: missing volume \"{}\"", context, key)),
+                Some(volume) if !(min..=100).contains(volume) => return Err(format!("{}: volume \"{}\" is {}, it must be between {} and 100", context, key, volume, min)),
+                Some(volume) => volumes.push(*volume as u8),
+            }
+        }
+        if let Some(key) = entry.volumes.keys().find(|key| !(0..STATE_COUNT as u8).any(|state| States::key(state) == key.as_str())) {
+            return Err(format!("{}: unknown volume \"{}\", expected one of: {}", context, key, (0..STATE_COUNT as u8).map(States::key).collect::<Vec<_>>().join(", ")));
+        }
+
+        let mut min_dwell_ms = Vec::new();
+        for state in 0..STATE_COUNT as u8 {
+            match entry.min_dwell_ms.get(States::key(state)) {
+                Some(time) if !(0..=10000).contains(time) => return Err(format!("{}: min_dwell_ms \"{}\" is {}, it must be between 0 and 10000", context, States::key(state), time)),
+                time => min_dwell_ms.push(time.copied().unwrap_or(0) as u32),
+            }
+        }
+        if let Some(key) = entry.min_dwell_ms.keys().find(|key| !(0..STATE_COUNT as u8).any(|state| States::key(state) == key.as_str())) {
+            return Err(format!("{}: unknown state \"{}\" in min_dwell_ms, expected one of: {}", context, key, (0..STATE_COUNT as u8).map(States::key).collect::<Vec<_>>().join(", ")));
+        }
+
+        let mut round_rules = Vec::new();
+        for (index, rule) in entry.round_rules.iter().enumerate() {
+            let state = match &rule.state {
+                None => None,
+                Some(key) => match States::ALL.into_iter().filter(|state| States::in_match(*state)).find(|state| States::key(*state) == key) {
+                    Some(state) => Some(state),
+                    None => return Err(format!("{}: round_rules[{}]: unknown state \"{}\", expected one of: buy_phase, playing", context, index, key)),
+                },
+            };
+            if !(0..=100).contains(&rule.volume) {
+                return Err(format!("{}: round_rules[{}]: volume is {}, it must be between 0 and 100", context, index, rule.volume));
+            }
+            round_rules.push(RoundRule { enabled: rule.enabled, when: rule.when, state, volume: rule.volume as u8 });
+        }
+
+        profiles.push(Profile {
+            name: entry.name,
+            volumes,
+            relative_mode: entry.relative_mode,
+            fade_duration_ms: entry.fade_duration_ms as u32,
+            pause_on_zero: entry.pause_on_zero,
+            process_name: entry.process_name,
+            min_dwell_ms,
+            early_fade_s: entry.early_fade_s as u32,
+            round_rules,
+        });
+    }
+
+    for (index, rule) in file.rules.iter().enumerate() {
+        if !profiles.iter().any(|profile| profile.name == rule.profile) {
+            return Err(format!("rules[{}]: there is no profile named \"{}\"", index, rule.profile));
+        }
+        if let Some(map) = &rule.map {
+            if !MAPS.iter().any(|(_, name)| name == map) {
+                return Err(format!("rules[{}]: unknown map \"{}\", expected one of: {}", index, map, MAPS.map(|(_, name)| name).join(", ")));
+            }
+        }
+    }
+
+    let Some(selected_profile) = profiles.iter().position(|profile| profile.name == file.selected_profile) else {
+        return Err(format!("selected_profile: there is no profile named \"{}\"", file.selected_profile));
+    };
+
+    Ok(Settings {
+        profiles,
+        selected_profile,
+        rules: file.rules,
+        log_to_file: file.log_to_file,
+        log_path: file.log_path,
+        minimize_to_tray: file.minimize_to_tray,
+        close_to_tray: file.close_to_tray,
+        start_minimized: file.start_minimized,
+        game_state_source: file.game_state_source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vmc-test-{}-{}", std::process::id(), name))
+    }
+
+    fn import_text(name: &str, contents: &str) -> Result<Settings, String> {
+        let path = temp_path(name);
+        std::fs::write(&path, contents).unwrap();
+        let result = import(&path);
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    // Archivo exportado con los ajustes por defecto, que se modifica en cada prueba
+    fn exported(name: &str) -> String {
+        let settings = Settings {
+            profiles: vec![Profile::default()],
+            selected_profile: 0,
+            rules: Vec::new(),
+            log_to_file: false,
+            log_path: String::new(),
+            minimize_to_tray: true,
+            close_to_tray: false,
+            start_minimized: false,
+            game_state_source: SourceKind::Log,
+        };
+        let path = temp_path(name);
+        export(&settings, &path).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        contents
+    }
+
+    #[test]
+    fn export_and_import() {
+        let settings = import_text("roundtrip.toml", &exported("roundtrip-export.toml")).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(settings.profiles.len(), 1);
+        assert_eq!(settings.profiles[0].volumes, Profile::default().volumes);
+        assert_eq!(settings.profiles[0].fade_duration_ms, Profile::default().fade_duration_ms);
+    }
+
+    #[test]
+    fn newer_version() {
+        let contents = exported("newer-export.toml").replace(&format!("version = {}", CURRENT_VERSION), "version = 99");
+        assert_eq!(
+            import_text("newer.toml", &contents).err(),
+            Some(format!("The settings file has version 99, but this version of the program only supports up to version {}. Update the program to import it.", CURRENT_VERSION))
+        );
+    }
+
+    #[test]
+    fn wrong_types() {
+        let contents = exported("types-export.toml").replace(&format!("version = {}", CURRENT_VERSION), "version = \"1\"");
+        assert_eq!(import_text("version-type.toml", &contents).err().as_deref(), Some("\"version\" must be an integer, found string"));
+
+        // Los errores de los archivos de la versión actual indican la línea
+        let contents = exported("types-export.toml").replace("fade_duration_ms = 1000", "fade_duration_ms = \"fast\"");
+        let err = import_text("fade-type.toml", &contents).err().unwrap();
+        assert!(err.starts_with("Invalid settings file:\n"), "{}", err);
+        assert!(err.contains("invalid type: string \"fast\", expected i64"), "{}", err);
+        assert!(err.contains("fade_duration_ms = \"fast\""), "{}", err);
+    }
+
+    #[test]
+    fn out_of_range_values() {
+        let contents = exported("range-export.toml").replace("playing = 0", "playing = 150");
+        assert_eq!(
+            import_text("volume-range.toml", &contents).err().as_deref(),
+            Some("profiles[0] (\"Default\"): volume \"playing\" is 150, it must be between 0 and 100")
+        );
+
+        // Fuera de partida no puede ser 0 porque la música se quedaría pausada
+        let contents = exported("range-export.toml").replace("not_in_game = 100", "not_in_game = 0");
+        assert_eq!(
+            import_text("not-in-game-range.toml", &contents).err().as_deref(),
+            Some("profiles[0] (\"Default\"): volume \"not_in_game\" is 0, it must be between 1 and 100")
+        );
+
+        let contents = exported("range-export.toml").replace("fade_duration_ms = 1000", "fade_duration_ms = 9000");
+        assert_eq!(
+            import_text("fade-range.toml", &contents).err(),
+            Some(format!("profiles[0] (\"Default\"): fade_duration_ms is 9000, it must be between 0 and {}", MAX_FADE_DURATION_MS))
+        );
+    }
+
+    #[test]
+    fn missing_version() {
+        let contents = exported("missing-export.toml").replace(&format!("version = {}\n", CURRENT_VERSION), "");
+        assert_eq!(import_text("missing-version.toml", &contents).err().as_deref(), Some("Missing \"version\""));
+    }
+
+    #[test]
+    fn migrate_version_1() {
+        // En la versión 1 solo había volúmenes fuera de partida, en la fase de compra y jugando
+        let contents = "\
+version = 1
+selected_profile = \"Ranked\"
+
+[[profiles]]
+name = \"Ranked\"
+fade_duration_ms = 500
+
+[profiles.volumes]
+not_in_game = 80
+buy_phase = 40
+playing = 10
+";
+        let settings = import_text("version-1.toml", contents).unwrap_or_else(|err| panic!("{}", err));
+        let profile = &settings.profiles[0];
+        assert_eq!((profile.name.as_str(), profile.fade_duration_ms), ("Ranked", 500));
+        assert_eq!(profile.volumes.len(), STATE_COUNT);
+        assert_eq!((profile.volume(States::NOT_IN_GAME), profile.volume(States::IN_GAME_PREPARING), profile.volume(States::IN_GAME_PLAYING)), (80, 40, 10));
+        // Los estados previos a la partida formaban parte de "No en partida"
+        for state in States::ALL.into_iter().filter(|state| States::is_pregame(*state)) {
+            assert_eq!(profile.volume(state), 80, "{}", States::name(state));
+        }
+
+        assert!(import_text("version-0.toml", &contents.replace("version = 1", "version = 0")).is_err());
+    }
+}
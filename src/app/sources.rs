@@ -0,0 +1,668 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use serde_json::Value;
+
+use super::constants::*;
+use super::matchers::GameEvent;
+
+// Tiempo máximo de espera de cada petición a la API local
+const API_TIMEOUT: Duration = Duration::from_secs(2);
+// En modo automático, cada cuánto se vuelve a probar la API mientras se lee el log
+const API_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+// De dónde se obtiene el estado del juego
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    #[default]
+    Log,
+    RiotApi,
+    Automatic, // La API local si está disponible; si no, o si deja de responder, el log
+}
+
+impl SourceKind {
+    pub const ALL: [SourceKind; 3] = [SourceKind::Log, SourceKind::RiotApi, SourceKind::Automatic];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceKind::Log => "Game log",
+            SourceKind::RiotApi => "Riot Client API",
+            SourceKind::Automatic => "Automatic",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            SourceKind::Log => "Read the log of the game. Detects every state and the round of the match, and the round winners with your own log matchers.",
+            SourceKind::RiotApi => "Ask the Riot Client for the state of the game. Doesn't depend on the log messages, but the buy phase is estimated and round winners aren't detected.",
+            SourceKind::Automatic => "Use the Riot Client API when it's available and the game log if it isn't or stops responding. While reading the game log, the API is tried again every 30 seconds.",
+        }
+    }
+}
+
+// Lo que obtiene una fuente en cada consulta
+pub enum SourceUpdate {
+    Line(String), // Línea del log, que se analiza con las reglas y se pasa al script
+    Event(GameEvent), // Evento ya interpretado por la fuente
+}
+
+// Fuente del estado del juego que el controlador consulta periódicamente
+pub trait GameStateSource {
+    fn name(&self) -> &'static str;
+
+    // Novedades desde la consulta anterior. Un error indica que la fuente no está disponible por ahora; se vuelve a
+    // intentar en la siguiente consulta.
+    fn poll(&mut self) -> Result<Vec<SourceUpdate>, String>;
+}
+
+// Lee las líneas nuevas del log del juego. Si el archivo todavía no existe (ej. el juego se acaba de abrir y aún no lo
+// ha creado), se abre en cuanto aparece y se lee desde el principio.
+pub struct LogTail {
+    path: PathBuf,
+    file: Option<File>,
+    pos: u64, // Posición del archivo hasta la que se ha leído
+}
+
+impl LogTail {
+    pub fn open(path: &Path, start: u64) -> Self {
+        Self { path: path.to_owned(), file: File::open(path).ok(), pos: start }
+    }
+
+    // Abre el log para leer solo lo que se escriba a partir de ahora
+    pub fn open_at_end(path: &Path) -> Self {
+        let len = std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or_default();
+        Self::open(path, len)
+    }
+
+    fn read(&mut self) -> std::io::Result<Vec<SourceUpdate>> {
+        // No se puede comprobar actualizaciones en el archivo por culpa de cómo funciona Windows; por tanto,
+        // se comprueba en cada consulta si el archivo ha sido modificado.
+        let len = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(err) => {
+                // Cuando vuelva a aparecer será un log nuevo
+                self.file = None;
+                self.pos = 0;
+                return Err(err);
+            }
+        };
+        // Al abrir el juego se crea un log nuevo, así que si es más corto que lo que se ha leído se lee desde el principio
+        if len < self.pos {
+            log::info!("The game log has been recreated, reading it from the start");
+            self.file = None;
+            self.pos = 0;
+        }
+        if len == self.pos { return Ok(Vec::new()); }
+
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => File::open(&self.path)?,
+        };
+        let file = self.file.insert(file);
+        file.seek(std::io::SeekFrom::Start(self.pos))?;
+        let mut reader = BufReader::new(&*file);
+        let mut updates = Vec::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            // Una línea sin salto de línea todavía se está escribiendo, así que se lee entera en la siguiente consulta
+            if read == 0 || line.last() != Some(&b'\n') { break; }
+            self.pos += read as u64;
+
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\r', '\n']);
+            if !text.is_empty() { updates.push(SourceUpdate::Line(text.to_owned())); }
+        }
+        Ok(updates)
+    }
+}
+
+impl GameStateSource for LogTail {
+    fn name(&self) -> &'static str { SourceKind::Log.name() }
+
+    fn poll(&mut self) -> Result<Vec<SourceUpdate>, String> {
+        self.read().map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => format!("Waiting for the game to create its log file {}", self.path.display()),
+            _ => format!("Couldn't read the game log: {}", err),
+        })
+    }
+}
+
+// Datos de conexión a la API local que el Riot Client escribe al abrirse | Riot Client:1234:56789:password:https |
+pub struct Lockfile {
+    pub port: u16,
+    pub password: String,
+    pub protocol: String,
+}
+
+impl Lockfile {
+    // Se puede usar otro lockfile con VMC_RIOT_LOCKFILE (ej. uno que apunte a un servidor local de pruebas)
+    pub fn path() -> PathBuf {
+        match std::env::var_os("VMC_RIOT_LOCKFILE") {
+            Some(path) => PathBuf::from(path),
+            None => PathBuf::from(std::env::var("LOCALAPPDATA").unwrap_or_default() + "\\Riot Games\\Riot Client\\Config\\lockfile"),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| format!("Couldn't read the Riot Client lockfile {} (is the Riot Client open?): {}", path.display(), err))?;
+        Self::parse(&contents).ok_or_else(|| format!("Invalid Riot Client lockfile {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Option<Self> {
+        let fields: Vec<&str> = contents.trim().split(':').collect();
+        let [_name, _pid, port, password, protocol] = fields[..] else { return None; };
+        Some(Self { port: port.parse().ok()?, password: password.to_owned(), protocol: protocol.to_owned() })
+    }
+}
+
+// Conexión a la API local del Riot Client y jugador con la sesión iniciada
+struct Connection {
+    agent: ureq::Agent,
+    url: String,
+    auth: String,
+    puuid: String,
+}
+
+impl Connection {
+    fn open(lockfile: &Path) -> Result<Self, String> {
+        let lockfile = Lockfile::read(lockfile)?;
+        // El certificado de la API local es autofirmado y solo se accede a 127.0.0.1, así que no se valida
+        let tls = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true)
+            .build()
+            .map_err(|err| format!("Couldn't create the TLS connector: {}", err))?;
+        let agent = ureq::AgentBuilder::new().timeout(API_TIMEOUT).tls_connector(Arc::new(tls)).build();
+        let auth = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("riot:{}", lockfile.password)));
+        let mut connection = Self { agent, url: format!("{}://127.0.0.1:{}", lockfile.protocol, lockfile.port), auth, puuid: String::new() };
+
+        let session = connection.get("/chat/v1/session")?;
+        connection.puuid = session["puuid"].as_str().filter(|puuid| !puuid.is_empty())
+            .ok_or("The Riot Client hasn't started a session, log in first")?
+            .to_owned();
+        log::info!("Connected to the Riot Client API");
+        Ok(connection)
+    }
+
+    fn get(&self, path: &str) -> Result<Value, String> {
+        let response = self.agent.get(&format!("{}{}", self.url, path)).set("Authorization", &self.auth).call()
+            .map_err(|err| format!("The Riot Client API didn't respond to {}: {}", path, err))?;
+        let body = response.into_string().map_err(|err| format!("Couldn't read the response to {}: {}", path, err))?;
+        serde_json::from_str(&body).map_err(|err| format!("Invalid response to {}: {}", path, err))
+    }
+
+    // Presencia de VALORANT del jugador; la de por defecto si el juego no está abierto
+    fn presence(&self) -> Result<Presence, String> {
+        let presences = self.get("/chat/v4/presences")?;
+        let Some(private) = presences["presences"].as_array().into_iter().flatten()
+            .find(|presence| presence["puuid"] == self.puuid.as_str() && presence["product"] == "valorant")
+            .and_then(|presence| presence["private"].as_str())
+        else { return Ok(Presence::default()); };
+
+        let decoded = base64::engine::general_purpose::STANDARD.decode(private).map_err(|err| format!("Invalid presence: {}", err))?;
+        let private = serde_json::from_slice(&decoded).map_err(|err| format!("Invalid presence: {}", err))?;
+        Ok(Presence::parse(&private))
+    }
+}
+
+// Estado de la sesión de VALORANT según la presencia del chat
+#[derive(Default, Clone, PartialEq, Debug)]
+struct Presence {
+    loop_state: String, // MENUS, PREGAME o INGAME; vacío si el juego no está abierto
+    party_state: String, // DEFAULT, MATCHMAKING, MATCHMADE_GAME_STARTING...
+    queue: String,
+    provisioning: String, // Matchmaking, CustomGame, ShootingRange...
+    map: String, // /Game/Maps/Ascent/Ascent
+    score: (u32, u32), // (aliados, enemigos)
+}
+
+impl Presence {
+    // Los campos están en la raíz en las versiones antiguas del cliente y agrupados por tema en las nuevas
+    fn parse(private: &Value) -> Self {
+        let field = |name: &str| [private, &private["matchPresenceData"], &private["partyPresenceData"]].into_iter().find_map(|data| data.get(name));
+        let text = |name: &str| field(name).and_then(|value| value.as_str()).unwrap_or_default().to_owned();
+        let number = |name: &str| field(name).and_then(|value| value.as_u64()).unwrap_or_default() as u32;
+        Self {
+            loop_state: text("sessionLoopState"),
+            party_state: text("partyState"),
+            queue: text("queueId"),
+            provisioning: text("provisioningFlow"),
+            map: text("matchMap"),
+            score: (number("partyOwnerMatchScoreAllyTeam"), number("partyOwnerMatchScoreEnemyTeam")),
+        }
+    }
+
+    fn in_match(&self) -> bool { self.loop_state == "INGAME" }
+
+    fn rounds(&self) -> u32 { self.score.0 + self.score.1 }
+
+    // Estado fuera de la partida
+    fn pregame_state(&self) -> u8 {
+        match (self.loop_state.as_str(), self.party_state.as_str()) {
+            ("", _) => States::NOT_IN_GAME,
+            ("PREGAME", _) => States::AGENT_SELECT,
+            (_, "MATCHMAKING") => States::IN_QUEUE,
+            (_, "MATCHMADE_GAME_STARTING") => States::MATCH_FOUND,
+            _ => States::MENUS,
+        }
+    }
+
+    fn mode(&self) -> Option<GameMode> {
+        match self.provisioning.as_str() {
+            "ShootingRange" => Some(GameMode::Range),
+            "CustomGame" => Some(GameMode::Custom),
+            _ => GameMode::from_queue(&self.queue),
+        }
+    }
+
+    fn map_name(&self) -> Option<&'static str> {
+        map_name(self.map.split('/').nth(3)?)
+    }
+}
+
+// Consulta la presencia del jugador en la API local del Riot Client (GET /chat/v1/session y /chat/v4/presences) y la
+// convierte en eventos. La presencia no indica las fases de la ronda: el final de cada ronda se deduce del marcador y su
+// inicio se estima con la duración de la fase de compra del modo. Si la API no responde (ej. el Riot Client no está
+// abierto o se está reiniciando) se vuelve a leer el lockfile en la siguiente consulta.
+pub struct RiotApi {
+    lockfile: PathBuf,
+    connection: Option<Connection>,
+    presence: Presence, // Presencia de la consulta anterior
+    reported: u8, // Último estado fuera de la partida que se ha comunicado
+    round_start: Option<Instant>, // Cuándo se espera que termine la fase de compra
+}
+
+impl RiotApi {
+    // Se conecta en la primera consulta
+    pub fn new(lockfile: &Path) -> Self {
+        Self { lockfile: lockfile.to_owned(), connection: None, presence: Presence::default(), reported: States::NOT_IN_GAME, round_start: None }
+    }
+
+    pub fn connect(lockfile: &Path) -> Result<Self, String> {
+        let mut api = Self::new(lockfile);
+        api.connection = Some(Connection::open(lockfile)?);
+        Ok(api)
+    }
+
+    fn expect_round_start(&mut self, updates: &mut Vec<SourceUpdate>, first_round: bool) {
+        match GameMode::buy_phase_end(self.presence.mode(), first_round) {
+            Some(buy_phase) => self.round_start = Some(Instant::now() + buy_phase),
+            None => updates.push(SourceUpdate::Event(GameEvent::RoundStarted)), // Modos sin fase de compra
+        }
+    }
+
+    // Eventos que produce el cambio de la presencia anterior a la nueva
+    fn update(&mut self, presence: Presence) -> Vec<SourceUpdate> {
+        let mut updates = Vec::new();
+        let previous = std::mem::replace(&mut self.presence, presence);
+        let presence = &self.presence;
+
+        if presence.in_match() && !previous.in_match() {
+            let rounds = presence.rounds();
+            if let Some(mode) = presence.mode() { updates.push(SourceUpdate::Event(GameEvent::GameModeLoaded(Some(mode)))); }
+            if let Some(map) = presence.map_name() { updates.push(SourceUpdate::Event(GameEvent::MapLoaded(map.to_owned()))); }
+            updates.push(SourceUpdate::Event(GameEvent::MatchStarted));
+            // Si se empieza a consultar en mitad de una partida, se continúa desde la última ronda terminada
+            if rounds > 0 { updates.push(SourceUpdate::Event(GameEvent::RoundEnded(rounds - 1, None))); }
+            self.expect_round_start(&mut updates, rounds == 0);
+        }
+        else if presence.in_match() {
+            if presence.rounds() > previous.rounds() {
+                updates.push(SourceUpdate::Event(GameEvent::RoundEnded(presence.rounds() - 1, None)));
+                self.expect_round_start(&mut updates, false);
+            }
+        }
+        else if previous.in_match() {
+            // Si el juego se cierra en mitad de la partida no hay resultado. El estado fuera de la partida se comunica en la siguiente consulta.
+            let event = if presence.loop_state.is_empty() { GameEvent::MatchAborted } else { GameEvent::MatchEnded(None) };
+            updates.push(SourceUpdate::Event(event));
+            self.reported = States::NOT_IN_GAME;
+            self.round_start = None;
+        }
+        else if presence.pregame_state() != self.reported {
+            self.reported = presence.pregame_state();
+            updates.push(SourceUpdate::Event(GameEvent::Pregame(self.reported)));
+        }
+        updates
+    }
+}
+
+impl GameStateSource for RiotApi {
+    fn name(&self) -> &'static str { SourceKind::RiotApi.name() }
+
+    fn poll(&mut self) -> Result<Vec<SourceUpdate>, String> {
+        let connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => Connection::open(&self.lockfile)?,
+        };
+        let presence = connection.presence()?; // Si falla, se vuelve a conectar en la siguiente consulta
+        self.connection = Some(connection);
+
+        let mut updates = Vec::new();
+        if self.round_start.is_some_and(|start| Instant::now() >= start) {
+            self.round_start = None;
+            updates.push(SourceUpdate::Event(GameEvent::RoundStarted));
+        }
+        updates.extend(self.update(presence));
+        Ok(updates)
+    }
+}
+
+// Modo automático: usa la API del Riot Client mientras responde y el log del juego mientras no. Desde el log se vuelve a
+// probar la API periódicamente, con una conexión nueva que parte del estado actual del juego.
+pub struct AutomaticSource {
+    lockfile: PathBuf,
+    api: Option<RiotApi>, // None mientras se lee el log
+    log: LogTail,
+    next_api_try: Instant,
+    retry_interval: Duration,
+}
+
+impl AutomaticSource {
+    // Si no se indica la API se empieza leyendo el log
+    pub fn new(lockfile: &Path, api: Option<RiotApi>, log: LogTail) -> Self {
+        Self { lockfile: lockfile.to_owned(), api, log, next_api_try: Instant::now() + API_RETRY_INTERVAL, retry_interval: API_RETRY_INTERVAL }
+    }
+
+    fn use_log(&mut self) -> Result<Vec<SourceUpdate>, String> {
+        if Instant::now() >= self.next_api_try {
+            self.next_api_try = Instant::now() + self.retry_interval;
+            let mut api = RiotApi::new(&self.lockfile);
+            match api.poll() {
+                Ok(updates) => {
+                    log::info!("The Riot Client API is available, switching to it");
+                    self.api = Some(api);
+                    return Ok(updates);
+                }
+                Err(err) => log::debug!("{}", err),
+            }
+        }
+        self.log.poll()
+    }
+}
+
+impl GameStateSource for AutomaticSource {
+    fn name(&self) -> &'static str {
+        if self.api.is_some() { SourceKind::RiotApi.name() } else { SourceKind::Log.name() }
+    }
+
+    fn poll(&mut self) -> Result<Vec<SourceUpdate>, String> {
+        let Some(api) = self.api.as_mut() else { return self.use_log(); };
+        match api.poll() {
+            Ok(updates) => Ok(updates),
+            Err(err) => {
+                // Lo que se escribió en el log mientras se usaba la API ya se ha aplicado
+                log::warn!("{}, switching to the game log", err);
+                self.api = None;
+                self.log = LogTail::open_at_end(&self.log.path);
+                self.next_api_try = Instant::now() + self.retry_interval;
+                self.log.poll()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use super::*;
+
+    const SESSION: &str = include_str!("../../tests/fixtures/riot_api/session.json");
+    const MENUS: &str = include_str!("../../tests/fixtures/riot_api/presences_menus.json");
+    const QUEUE: &str = include_str!("../../tests/fixtures/riot_api/presences_queue.json");
+    const PREGAME: &str = include_str!("../../tests/fixtures/riot_api/presences_pregame.json");
+    const INGAME: &str = include_str!("../../tests/fixtures/riot_api/presences_ingame.json");
+    const NEXT_ROUND: &str = include_str!("../../tests/fixtures/riot_api/presences_ingame_next_round.json");
+    const MATCH_END: &str = include_str!("../../tests/fixtures/riot_api/presences_match_end.json");
+    const PASSWORD: &str = "Zx8qLw3nVb5tKc7y";
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vmc-test-{}-{}", std::process::id(), name))
+    }
+
+    fn lines(updates: Vec<SourceUpdate>) -> Vec<String> {
+        updates.into_iter().map(|update| match update {
+            SourceUpdate::Line(line) => line,
+            SourceUpdate::Event(event) => panic!("Unexpected event {:?}", event),
+        }).collect()
+    }
+
+    fn events(updates: Vec<SourceUpdate>) -> Vec<GameEvent> {
+        updates.into_iter().map(|update| match update {
+            SourceUpdate::Event(event) => event,
+            SourceUpdate::Line(line) => panic!("Unexpected line {}", line),
+        }).collect()
+    }
+
+    #[test]
+    fn log_tail_waits_for_complete_lines() {
+        let path = temp_path("ShooterGame.log");
+        std::fs::write(&path, "[old]Log: before opening\n").unwrap();
+        let mut tail = LogTail::open_at_end(&path);
+        assert!(tail.poll().unwrap().is_empty());
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"[1]Log: first\r\n\n[2]Log: sec").unwrap();
+        assert_eq!(lines(tail.poll().unwrap()), ["[1]Log: first"]);
+        file.write_all(b"ond\n").unwrap();
+        assert_eq!(lines(tail.poll().unwrap()), ["[2]Log: second"]);
+        assert!(tail.poll().unwrap().is_empty());
+
+        // Al abrir el juego el log se crea de nuevo
+        std::fs::write(&path, "[3]Log: new\n").unwrap();
+        assert_eq!(lines(tail.poll().unwrap()), ["[3]Log: new"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn log_tail_waits_for_the_file() {
+        // El juego todavía no ha creado el log al activar el programa
+        let path = temp_path("missing-ShooterGame.log");
+        let _ = std::fs::remove_file(&path);
+        let mut tail = LogTail::open(&path, 0);
+        assert!(tail.poll().err().unwrap().starts_with("Waiting for the game to create its log file"));
+        assert!(tail.poll().is_err());
+
+        // En cuanto aparece se lee entero
+        std::fs::write(&path, "[1]Log: first\n[2]Log: second\n").unwrap();
+        assert_eq!(lines(tail.poll().unwrap()), ["[1]Log: first", "[2]Log: second"]);
+
+        // Si se borra, el siguiente log se lee desde el principio aunque sea más largo
+        std::fs::remove_file(&path).unwrap();
+        assert!(tail.poll().is_err());
+        std::fs::write(&path, "[3]Log: a longer first line of the new log\n").unwrap();
+        assert_eq!(lines(tail.poll().unwrap()), ["[3]Log: a longer first line of the new log"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn lockfile() {
+        let lockfile = Lockfile::parse("Riot Client:10452:54321:Zx8qLw3nVb5tKc7y:https\n").unwrap();
+        assert_eq!((lockfile.port, lockfile.password.as_str(), lockfile.protocol.as_str()), (54321, PASSWORD, "https"));
+
+        assert!(Lockfile::parse("").is_none());
+        assert!(Lockfile::parse("Riot Client:10452:54321:Zx8qLw3nVb5tKc7y").is_none());
+        assert!(Lockfile::parse("Riot Client:10452:port:Zx8qLw3nVb5tKc7y:https").is_none());
+    }
+
+    #[test]
+    fn flat_presence() {
+        let private = serde_json::json!({
+            "isValid": true,
+            "sessionLoopState": "INGAME",
+            "partyState": "DEFAULT",
+            "queueId": "unrated",
+            "provisioningFlow": "Matchmaking",
+            "matchMap": "/Game/Maps/Bonsai/Bonsai",
+            "partyOwnerMatchScoreAllyTeam": 4,
+            "partyOwnerMatchScoreEnemyTeam": 2,
+        });
+        let presence = Presence::parse(&private);
+        assert!(presence.in_match());
+        assert_eq!(presence.rounds(), 6);
+        assert_eq!(presence.mode(), Some(GameMode::Unrated));
+        assert_eq!(presence.map_name(), Some("Split"));
+    }
+
+    #[test]
+    fn nested_presence() {
+        let private = serde_json::json!({
+            "isValid": true,
+            "matchPresenceData": { "sessionLoopState": "MENUS", "provisioningFlow": "Invalid", "matchMap": "", "queueId": "competitive" },
+            "partyPresenceData": { "partyState": "MATCHMAKING", "partyOwnerMatchScoreAllyTeam": 0, "partyOwnerMatchScoreEnemyTeam": 0 },
+        });
+        let presence = Presence::parse(&private);
+        assert!(!presence.in_match());
+        assert_eq!(presence.pregame_state(), States::IN_QUEUE);
+        assert_eq!(presence.mode(), Some(GameMode::Competitive));
+        assert_eq!(presence.map_name(), None);
+
+        let custom = serde_json::json!({ "matchPresenceData": { "sessionLoopState": "PREGAME", "provisioningFlow": "CustomGame", "queueId": "" } });
+        let presence = Presence::parse(&custom);
+        assert_eq!(presence.pregame_state(), States::AGENT_SELECT);
+        assert_eq!(presence.mode(), Some(GameMode::Custom));
+        assert_eq!(Presence::parse(&serde_json::json!({})).pregame_state(), States::NOT_IN_GAME);
+    }
+
+    // Riot Client falso: servidor HTTP que responde a la API local con los archivos de prueba, escritos a mano con el
+    // formato de la API. presences es la respuesta actual de /chat/v4/presences; si está vacía, el cliente no responde.
+    fn fake_riot_client(presences: Arc<Mutex<&'static str>>) -> u16 {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let auth = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("riot:{}", PASSWORD)));
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+                let authorized = request.lines().any(|line| line.eq_ignore_ascii_case(&format!("authorization: {}", auth)));
+                let (status, body) = match path {
+                    _ if !authorized => ("401 Unauthorized", "{}"),
+                    _ if presences.lock().unwrap().is_empty() => ("503 Service Unavailable", "{}"),
+                    "/chat/v1/session" => ("200 OK", SESSION),
+                    "/chat/v4/presences" => ("200 OK", *presences.lock().unwrap()),
+                    _ => ("404 Not Found", "{}"),
+                };
+                let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn riot_api_match() {
+        let presences = Arc::new(Mutex::new(MENUS));
+        let port = fake_riot_client(presences.clone());
+        let lockfile = temp_path("lockfile");
+        std::fs::write(&lockfile, format!("Riot Client:10452:{}:{}:http", port, PASSWORD)).unwrap();
+
+        let mut api = RiotApi::connect(&lockfile).unwrap();
+        let mut poll = |response: &'static str| {
+            *presences.lock().unwrap() = response;
+            events(api.poll().unwrap())
+        };
+        assert_eq!(poll(MENUS), [GameEvent::Pregame(States::MENUS)]);
+        assert_eq!(poll(MENUS), []);
+        assert_eq!(poll(QUEUE), [GameEvent::Pregame(States::IN_QUEUE)]);
+        assert_eq!(poll(PREGAME), [GameEvent::Pregame(States::AGENT_SELECT)]);
+        // Se empieza a consultar en mitad de la partida (5-3), así que se continúa desde la última ronda terminada
+        assert_eq!(poll(INGAME), [
+            GameEvent::GameModeLoaded(Some(GameMode::Competitive)),
+            GameEvent::MapLoaded("Ascent".to_owned()),
+            GameEvent::MatchStarted,
+            GameEvent::RoundEnded(7, None),
+        ]);
+        assert_eq!(poll(INGAME), []);
+        assert_eq!(poll(NEXT_ROUND), [GameEvent::RoundEnded(8, None)]);
+        assert_eq!(poll(MATCH_END), [GameEvent::MatchEnded(None)]);
+        assert_eq!(poll(MATCH_END), [GameEvent::Pregame(States::MENUS)]);
+        let _ = std::fs::remove_file(&lockfile);
+    }
+
+    #[test]
+    fn riot_api_wrong_password() {
+        let port = fake_riot_client(Arc::new(Mutex::new(MENUS)));
+        let lockfile = temp_path("wrong-lockfile");
+        std::fs::write(&lockfile, format!("Riot Client:10452:{}:wrong:http", port)).unwrap();
+        assert!(RiotApi::connect(&lockfile).is_err());
+        let _ = std::fs::remove_file(&lockfile);
+    }
+
+    #[test]
+    fn riot_api_reconnects() {
+        // El Riot Client todavía no está abierto al activar el programa
+        let lockfile = temp_path("later-lockfile");
+        let _ = std::fs::remove_file(&lockfile);
+        let mut api = RiotApi::new(&lockfile);
+        assert!(api.poll().is_err());
+
+        let presences = Arc::new(Mutex::new(MENUS));
+        let port = fake_riot_client(presences.clone());
+        std::fs::write(&lockfile, format!("Riot Client:10452:{}:{}:http", port, PASSWORD)).unwrap();
+        assert_eq!(events(api.poll().unwrap()), [GameEvent::Pregame(States::MENUS)]);
+
+        // Si deja de responder se vuelve a conectar en cuanto responde otra vez
+        *presences.lock().unwrap() = "";
+        assert!(api.poll().is_err());
+        *presences.lock().unwrap() = QUEUE;
+        assert_eq!(events(api.poll().unwrap()), [GameEvent::Pregame(States::IN_QUEUE)]);
+        let _ = std::fs::remove_file(&lockfile);
+    }
+
+    #[test]
+    fn automatic_source() {
+        let presences = Arc::new(Mutex::new(MENUS));
+        let port = fake_riot_client(presences.clone());
+        let lockfile = temp_path("automatic-lockfile");
+        std::fs::write(&lockfile, format!("Riot Client:10452:{}:{}:http", port, PASSWORD)).unwrap();
+        let log_path = temp_path("automatic-ShooterGame.log");
+        std::fs::write(&log_path, "[1]Log: before\n").unwrap();
+
+        let mut source = AutomaticSource::new(&lockfile, Some(RiotApi::connect(&lockfile).unwrap()), LogTail::open_at_end(&log_path));
+        assert_eq!(source.name(), SourceKind::RiotApi.name());
+        assert_eq!(events(source.poll().unwrap()), [GameEvent::Pregame(States::MENUS)]);
+
+        // Si la API deja de responder se pasa al log, desde lo que se escriba a partir de ahora
+        *presences.lock().unwrap() = "";
+        std::fs::OpenOptions::new().append(true).open(&log_path).unwrap().write_all(b"[2]Log: while using the API\n").unwrap();
+        assert!(source.poll().unwrap().is_empty());
+        assert_eq!(source.name(), SourceKind::Log.name());
+        std::fs::OpenOptions::new().append(true).open(&log_path).unwrap().write_all(b"[3]Log: after switching\n").unwrap();
+        assert_eq!(lines(source.poll().unwrap()), ["[3]Log: after switching"]);
+
+        // La API se vuelve a probar cada cierto tiempo, y al responder se vuelve a usar
+        source.retry_interval = Duration::ZERO;
+        source.next_api_try = Instant::now();
+        assert!(source.poll().unwrap().is_empty());
+        assert_eq!(source.name(), SourceKind::Log.name());
+        *presences.lock().unwrap() = INGAME;
+        assert_eq!(events(source.poll().unwrap()), [
+            GameEvent::GameModeLoaded(Some(GameMode::Competitive)),
+            GameEvent::MapLoaded("Ascent".to_owned()),
+            GameEvent::MatchStarted,
+            GameEvent::RoundEnded(7, None),
+        ]);
+        assert_eq!(source.name(), SourceKind::RiotApi.name());
+
+        let _ = std::fs::remove_file(&lockfile);
+        let _ = std::fs::remove_file(&log_path);
+    }
+}
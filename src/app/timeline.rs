@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use egui_plot::{Legend, Line, Plot, PlotPoints, Polygon};
+
+use super::constants::*;
+use super::events::ControllerEvent;
+
+// Número máximo de sesiones (partidas o simulaciones) que se guardan en la gráfica
+const MAX_SESSIONS: usize = 10;
+
+// Tramo de tiempo en el que el controlador estuvo en un estado
+struct StateBand {
+    state: u8,
+    start: f64,
+    end: Option<f64>, // None si es el tramo actual
+}
+
+// Volumen aplicado a lo largo del tiempo durante una partida, una activación o una simulación
+struct TimelineSession {
+    label: String,
+    start: Instant,
+    volume: Vec<[f64; 2]>, // (segundos desde el inicio, volumen en %)
+    bands: Vec<StateBand>,
+    simulation: bool,
+    live: bool,
+}
+
+impl TimelineSession {
+    fn new(label: &str, state: u8, volume: Option<f64>, simulation: bool) -> Self {
+        Self {
+            label: format!("{} {}", label, chrono::Local::now().format("%H:%M:%S")),
+            start: Instant::now(),
+            volume: volume.map(|volume| vec![[0.0, volume]]).unwrap_or_default(),
+            bands: vec![StateBand { state, start: 0.0, end: None }],
+            simulation,
+            live: true,
+        }
+    }
+
+    fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn finish(&mut self) {
+        let now = self.elapsed();
+        if let Some(band) = self.bands.last_mut() { band.end.get_or_insert(now); }
+        if let Some(&[_, volume]) = self.volume.last() { self.volume.push([now, volume]); }
+        self.live = false;
+    }
+
+    fn has_played(&self) -> bool {
+        self.bands.iter().any(|band| States::in_match(band.state))
+    }
+}
+
+#[derive(Default)]
+pub struct Timeline {
+    sessions: VecDeque<TimelineSession>,
+    selected: Option<usize>, // None para seguir siempre la sesión más reciente
+}
+
+impl Timeline {
+    pub fn apply(&mut self, event: &ControllerEvent) {
+        match event {
+            ControllerEvent::Started { state, simulation } => {
+                self.push(TimelineSession::new(if *simulation { "Simulation" } else { "Session" }, *state, None, *simulation));
+            }
+            ControllerEvent::StateChanged { prev, next } => {
+                let Some(session) = self.sessions.back_mut() else { return; };
+
+                // Cada partida empieza una sesión nueva, salvo que la sesión actual todavía no tenga ninguna
+                if !States::in_match(*prev) && States::in_match(*next) && !session.simulation {
+                    if session.has_played() {
+                        let volume = session.volume.last().map(|&[_, volume]| volume);
+                        session.finish();
+                        self.push(TimelineSession::new("Match", *next, volume, false));
+                        return;
+                    }
+                    session.label = format!("Match {}", chrono::Local::now().format("%H:%M:%S"));
+                }
+
+                let now = session.elapsed();
+                if let Some(band) = session.bands.last_mut() { band.end = Some(now); }
+                session.bands.push(StateBand { state: *next, start: now, end: None });
+            }
+            ControllerEvent::VolumeChanged { volume, .. } => {
+                if let Some(session) = self.sessions.back_mut() {
+                    let now = session.elapsed();
+                    session.volume.push([now, *volume as f64 * 100.0]);
+                }
+            }
+            ControllerEvent::Stopped => {
+                if let Some(session) = self.sessions.back_mut() { session.finish(); }
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, session: TimelineSession) {
+        if let Some(last) = self.sessions.back_mut() {
+            if last.live { last.finish(); }
+        }
+        if self.sessions.len() == MAX_SESSIONS {
+            self.sessions.pop_front();
+            self.selected = self.selected.and_then(|index| index.checked_sub(1));
+        }
+        self.sessions.push_back(session);
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        if self.sessions.is_empty() {
+            ui.label("There is nothing to show yet. Activate the program or simulate a match.");
+            return;
+        }
+
+        let latest = self.sessions.len() - 1;
+        let selected_label = match self.selected {
+            Some(index) => self.sessions[index].label.clone(),
+            None => format!("Latest ({})", self.sessions[latest].label),
+        };
+        egui::ComboBox::from_label("Session")
+        .selected_text(selected_label)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut self.selected, None, "Latest");
+            for (index, session) in self.sessions.iter().enumerate().rev() {
+                ui.selectable_value(&mut self.selected, Some(index), session.label.clone());
+            }
+        });
+
+        let session = &self.sessions[self.selected.unwrap_or(latest)];
+        let now = if session.live { session.elapsed() } else { session.bands.last().and_then(|band| band.end).unwrap_or(0.0) };
+
+        let mut volume = session.volume.clone();
+        if session.live {
+            if let Some(&[_, last]) = volume.last() { volume.push([now, last]); } // El volumen se mantiene hasta el momento actual
+        }
+
+        Plot::new("volume_timeline")
+        .legend(Legend::default())
+        .include_y(0.0)
+        .include_y(100.0)
+        .include_x(0.0)
+        .y_axis_label("Volume (%)")
+        .x_axis_formatter(|mark, _| format!("{}:{:02}", mark.value as i64 / 60, mark.value as i64 % 60))
+        .allow_scroll(false)
+        .show(ui, |plot_ui| {
+            for band in session.bands.iter() {
+                let end = band.end.unwrap_or(now);
+                plot_ui.polygon(
+                    Polygon::new(PlotPoints::from(vec![[band.start, 0.0], [end, 0.0], [end, 100.0], [band.start, 100.0]]))
+                    .fill_color(state_color(band.state).gamma_multiply(0.25))
+                    .stroke(egui::Stroke::NONE)
+                    .name(States::name(band.state))
+                );
+            }
+            plot_ui.line(Line::new(PlotPoints::from(volume)).name("Volume").width(2.0));
+        });
+    }
+}
+
+pub fn state_color(state: u8) -> egui::Color32 {
+    match state {
+        States::IN_GAME_PREPARING => egui::Color32::from_rgb(60, 140, 230),
+        States::IN_GAME_PLAYING => egui::Color32::from_rgb(230, 70, 70),
+        States::MENUS => egui::Color32::from_rgb(120, 120, 140),
+        States::IN_QUEUE => egui::Color32::from_rgb(150, 90, 200),
+        States::MATCH_FOUND => egui::Color32::from_rgb(220, 190, 60),
+        States::AGENT_SELECT => egui::Color32::from_rgb(70, 180, 110),
+        States::LOADING => egui::Color32::from_rgb(90, 110, 130),
+        _ => egui::Color32::GRAY,
+    }
+}
@@ -0,0 +1,167 @@
+use super::profiles::Profile;
+
+// Acciones del menú (o del icono) de la bandeja del sistema
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub enum TrayAction {
+    Show,
+    Toggle, // Activar o parar el programa
+    SelectProfile(usize),
+    Quit,
+}
+
+// Icono en la bandeja del sistema. Solo está disponible en Windows; en el resto de sistemas Tray::new() retorna None.
+#[cfg(windows)]
+pub struct Tray {
+    icon: tray_icon::TrayIcon,
+    state_icons: Vec<tray_icon::Icon>,
+    actions: std::sync::mpsc::Receiver<TrayAction>,
+    state: Option<u8>,
+    menu_key: String, // Datos con los que se creó el menú, para crearlo de nuevo solo si cambian
+}
+
+#[cfg(windows)]
+impl Tray {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Option<Self> {
+        use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+        use tray_icon::menu::MenuEvent;
+        use tray_icon::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+
+        let hwnd = match cc.window_handle().map(|handle| handle.as_raw()) {
+            Ok(RawWindowHandle::Win32(handle)) => handle.hwnd.get(),
+            _ => return None,
+        };
+
+        let state_icons: Vec<tray_icon::Icon> = (0..super::constants::STATE_COUNT as u8)
+            .filter_map(|state| tray_icon::Icon::from_rgba(state_icon_rgba(state), ICON_SIZE, ICON_SIZE).ok())
+            .collect();
+        let icon = match TrayIconBuilder::new().with_tooltip(super::APP_NAME).with_icon(state_icons.first()?.clone()).build() {
+            Ok(icon) => icon,
+            Err(err) => {
+                log::warn!("Couldn't create the tray icon: {}", err);
+                return None;
+            }
+        };
+
+        // Los eventos llegan aunque la ventana esté oculta, así que se pide que se vuelva a dibujar para que se procesen.
+        // La ventana se muestra directamente desde aquí por si al estar oculta no se procesan los eventos de la interfaz.
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = cc.egui_ctx.clone();
+        let send = move |action: TrayAction| {
+            if action == TrayAction::Show { show_window(hwnd); }
+            let _ = tx.send(action);
+            ctx.request_repaint();
+        };
+        let menu_send = send.clone();
+        MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+            let action = match event.id.0.as_str() {
+                "show" => Some(TrayAction::Show),
+                "toggle" => Some(TrayAction::Toggle),
+                "quit" => Some(TrayAction::Quit),
+                id => id.strip_prefix("profile:").and_then(|index| index.parse().ok()).map(TrayAction::SelectProfile),
+            };
+            if let Some(action) = action { menu_send(action); }
+        }));
+        TrayIconEvent::set_event_handler(Some(move |event: TrayIconEvent| {
+            if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                send(TrayAction::Show);
+            }
+        }));
+
+        Some(Self { icon, state_icons, actions: rx, state: None, menu_key: String::new() })
+    }
+
+    pub fn actions(&self) -> Vec<TrayAction> {
+        self.actions.try_iter().collect()
+    }
+
+    // Actualiza el icono según el estado y el menú según el programa y los perfiles
+    pub fn update(&mut self, state: Option<u8>, program_active: bool, profiles: &[Profile], selected: usize) {
+        use tray_icon::menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+
+        if state != self.state {
+            self.state = state;
+            let _ = self.icon.set_icon(self.state_icons.get(state.unwrap_or(0) as usize).cloned());
+            let tooltip = match state {
+                Some(state) => format!("{} - {}", super::APP_NAME, super::constants::States::name(state)),
+                None => format!("{} - Inactive", super::APP_NAME),
+            };
+            let _ = self.icon.set_tooltip(Some(tooltip));
+        }
+
+        let names: Vec<&str> = profiles.iter().map(|profile| profile.name.as_str()).collect();
+        let menu_key = format!("{}|{}|{}", program_active, selected, names.join("|"));
+        if menu_key == self.menu_key { return; }
+        self.menu_key = menu_key;
+
+        let profile_items: Vec<CheckMenuItem> = names.iter().enumerate()
+            .map(|(index, name)| CheckMenuItem::with_id(format!("profile:{}", index), name, true, index == selected, None))
+            .collect();
+        let profile_refs: Vec<&dyn IsMenuItem> = profile_items.iter().map(|item| item as &dyn IsMenuItem).collect();
+        let Ok(profile_menu) = Submenu::with_items("Profile", true, &profile_refs) else { return; };
+
+        let show = MenuItem::with_id("show", "Show window", true, None);
+        let toggle = MenuItem::with_id("toggle", if program_active { "Stop program" } else { "Activate program" }, true, None);
+        let quit = MenuItem::with_id("quit", "Quit", true, None);
+        if let Ok(menu) = Menu::with_items(&[&show, &PredefinedMenuItem::separator(), &toggle, &profile_menu, &PredefinedMenuItem::separator(), &quit]) {
+            self.icon.set_menu(Some(Box::new(menu)));
+        }
+    }
+}
+
+#[cfg(windows)]
+const ICON_SIZE: u32 = 32;
+
+// Icono de la aplicación con un círculo del color del estado en la esquina inferior derecha
+#[cfg(windows)]
+fn state_icon_rgba(state: u8) -> Vec<u8> {
+    let base = eframe::icon_data::from_png_bytes(&include_bytes!("../../assets/icon-256.png")[..]).unwrap();
+    let color = super::timeline::state_color(state);
+    let size = ICON_SIZE as usize;
+    let (center, radius) = (size as f32 * 0.75, size as f32 * 0.22);
+
+    let mut rgba = Vec::with_capacity(size * size * 4);
+    for y in 0..size {
+        for x in 0..size {
+            let distance = ((x as f32 + 0.5 - center).powi(2) + (y as f32 + 0.5 - center).powi(2)).sqrt();
+            if distance <= radius {
+                rgba.extend_from_slice(&[color.r(), color.g(), color.b(), 255]);
+            }
+            else if distance <= radius + 1.5 {
+                rgba.extend_from_slice(&[255, 255, 255, 255]); // Borde para que se vea sobre cualquier fondo
+            }
+            else {
+                // Se reduce el icono original tomando el píxel más cercano
+                let (source_x, source_y) = (x * base.width as usize / size, y * base.height as usize / size);
+                let index = (source_y * base.width as usize + source_x) * 4;
+                rgba.extend_from_slice(&base.rgba[index..index + 4]);
+            }
+        }
+    }
+    rgba
+}
+
+#[cfg(windows)]
+fn show_window(hwnd: isize) {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{SetForegroundWindow, ShowWindow, SW_RESTORE};
+    unsafe {
+        ShowWindow(hwnd, SW_RESTORE);
+        SetForegroundWindow(hwnd);
+    }
+}
+
+#[cfg(not(windows))]
+pub struct Tray;
+
+#[cfg(not(windows))]
+impl Tray {
+    pub fn new(_cc: &eframe::CreationContext<'_>) -> Option<Self> {
+        None
+    }
+
+    pub fn actions(&self) -> Vec<TrayAction> {
+        Vec::new()
+    }
+
+    pub fn update(&mut self, _state: Option<u8>, _program_active: bool, _profiles: &[Profile], _selected: usize) {}
+}
@@ -1,4 +1,4 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
-pub use app::ValorantMusicControllerApp;
+pub use app::{init_logger, ValorantMusicControllerApp, APP_NAME};
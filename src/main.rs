@@ -4,11 +4,11 @@
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
-    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    valorant_music_controller_gui::init_logger(); // Log to stderr (if you run with `RUST_LOG=debug`) and to the in-app log viewer.
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([550.0, 260.0])
+            .with_inner_size([550.0, 480.0])
             .with_min_inner_size([550.0, 250.0])
             .with_icon(
                 eframe::icon_data::from_png_bytes(&include_bytes!("../assets/icon-256.png")[..]).expect("Failed to load icon"),
@@ -17,7 +17,7 @@ fn main() -> eframe::Result {
     };
     
     eframe::run_native(
-        "Valorant Music Controller (GUI)",
+        valorant_music_controller_gui::APP_NAME,
         native_options,
         Box::new(|cc| Ok(Box::new(valorant_music_controller_gui::ValorantMusicControllerApp::new(cc)))),
     )